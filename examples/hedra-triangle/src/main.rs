@@ -7,7 +7,7 @@ use std::time::Instant;
 
 use hedra::math::{Vec2, Zero};
 use hedra::raster::simd::SimdTriangleRasterizer;
-use hedra::raster::{Rasterizer, Tile};
+use hedra::raster::{Rasterizer, RenderTarget, Tile};
 
 use softbuffer::{Context, Surface};
 use winit::application::ApplicationHandler;
@@ -74,7 +74,10 @@ impl ApplicationHandler for App {
 
                 data.rast.rasterize(
                     Tile {
-                        dst: &mut buffer,
+                        targets: [RenderTarget::new(&mut buffer)],
+                        depth: None,
+                        hiz: None,
+                        fragments: None,
                         dst_width: size.width as usize,
                         position: Vec2 { x: 16, y: 16 },
                         dimensions: Vec2 { x: 64, y: 64 },
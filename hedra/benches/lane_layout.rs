@@ -0,0 +1,142 @@
+#![feature(portable_simd)]
+
+//! Compares the lane coverage efficiency and speed of the SIMD rasterizer's lane layouts.
+//!
+//! Efficiency is the ratio of pixels covered by a triangle to lanes handed to the pixel
+//! closure, i.e. how many lanes of each shaded vector do useful work.
+
+use std::{
+    cell::Cell,
+    hint::black_box,
+    simd::{Mask, Simd},
+    time::Instant,
+};
+
+use hedra::{
+    math::{Vec2, Zero},
+    raster::{
+        simd::{
+            layout::{BlockLayout, RowLayout},
+            SimdTrianglePixelState, SimdTriangleRasterizer,
+        },
+        Rasterizer, RenderTarget, Tile,
+    },
+};
+
+const SIZE: usize = 256;
+const ITERATIONS: usize = 1000;
+
+fn bench<R>(rast: &mut R, list: &[Vec2<i32>]) -> (f64, f64)
+where
+    for<'a> R: Rasterizer<
+        'a,
+        i32,
+        State = SimdTrianglePixelState<i32, 64>,
+        Color = Simd<u32, 64>,
+        Mask = Mask<i32, 64>,
+    >,
+{
+    let mut dst = vec![0; SIZE * SIZE];
+    let invocations = Cell::new(0);
+
+    rast.rasterize(
+        Tile {
+            targets: [RenderTarget::new(&mut dst)],
+            depth: None,
+            hiz: None,
+            fragments: None,
+            dst_width: SIZE,
+            position: Vec2 { x: 0, y: 0 },
+            dimensions: Vec2 { x: SIZE, y: SIZE },
+        },
+        list,
+        |_| {
+            invocations.set(invocations.get() + 1);
+            !Simd::<u32, 64>::ZERO
+        },
+    );
+
+    let covered = dst.iter().filter(|&&c| c != 0).count();
+    let efficiency = covered as f64 / (invocations.get() * 64) as f64;
+
+    let instant = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        rast.rasterize(
+            Tile {
+                targets: [RenderTarget::new(&mut dst)],
+                depth: None,
+                hiz: None,
+                fragments: None,
+                dst_width: SIZE,
+                position: Vec2 { x: 0, y: 0 },
+                dimensions: Vec2 { x: SIZE, y: SIZE },
+            },
+            black_box(list),
+            |_| !Simd::<u32, 64>::ZERO,
+        );
+    }
+
+    let micros = instant.elapsed().as_secs_f64() * 1e6 / ITERATIONS as f64;
+
+    (efficiency, micros)
+}
+
+fn main() {
+    let triangles: [(&str, [Vec2<i32>; 3]); 4] = [
+        (
+            "large",
+            [
+                Vec2 { x: 8, y: 8 },
+                Vec2 { x: 248, y: 24 },
+                Vec2 { x: 128, y: 240 },
+            ],
+        ),
+        (
+            "small",
+            [
+                Vec2 { x: 100, y: 100 },
+                Vec2 { x: 112, y: 104 },
+                Vec2 { x: 104, y: 114 },
+            ],
+        ),
+        (
+            "thin horizontal",
+            [
+                Vec2 { x: 8, y: 120 },
+                Vec2 { x: 248, y: 124 },
+                Vec2 { x: 8, y: 126 },
+            ],
+        ),
+        (
+            "thin vertical",
+            [
+                Vec2 { x: 130, y: 8 },
+                Vec2 { x: 136, y: 248 },
+                Vec2 { x: 128, y: 248 },
+            ],
+        ),
+    ];
+
+    let mut row = SimdTriangleRasterizer::<i32, 64, RowLayout>::default();
+    let mut block = SimdTriangleRasterizer::<i32, 64, BlockLayout>::default();
+
+    println!(
+        "{:<16} {:>14} {:>14} {:>14} {:>14}",
+        "triangle", "row lanes", "row us", "8x8 lanes", "8x8 us"
+    );
+
+    for (name, list) in &triangles {
+        let (row_efficiency, row_micros) = bench(&mut row, list);
+        let (block_efficiency, block_micros) = bench(&mut block, list);
+
+        println!(
+            "{:<16} {:>13.1}% {:>14.2} {:>13.1}% {:>14.2}",
+            name,
+            row_efficiency * 100.0,
+            row_micros,
+            block_efficiency * 100.0,
+            block_micros
+        );
+    }
+}
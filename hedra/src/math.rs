@@ -8,6 +8,10 @@ macro_rules! num_trait_impl {
         impl One for $t {
             const ONE: Self = 1.0;
         }
+        impl Bounded for $t {
+            const MIN: Self = $t::MIN;
+            const MAX: Self = $t::MAX;
+        }
     };
 
     (f32) => {
@@ -24,6 +28,10 @@ macro_rules! num_trait_impl {
         impl One for $t {
             const ONE: Self = 1;
         }
+        impl Bounded for $t {
+            const MIN: Self = $t::MIN;
+            const MAX: Self = $t::MAX;
+        }
     };
 
     () => {
@@ -105,24 +113,30 @@ pub trait One {
     const ONE: Self;
 }
 
+/// Smallest and largest finite values of a number type.
+pub trait Bounded {
+    const MIN: Self;
+    const MAX: Self;
+}
+
 pub trait Cartesian<T> {
     fn to_barycentric(self, v1: Vec2<T>, v2: Vec2<T>, v3: Vec2<T>) -> Vec3<T>;
 }
 
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Vec2<T> {
     pub x: T,
     pub y: T,
 }
 
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Vec3<T> {
     pub x: T,
     pub y: T,
     pub z: T,
 }
 
-#[derive(Default, Debug, Clone, Copy)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Vec4<T> {
     pub x: T,
     pub y: T,
@@ -130,6 +144,13 @@ pub struct Vec4<T> {
     pub w: T,
 }
 
+/// Axis-aligned rectangle with its top-left corner at `position`.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect<T> {
+    pub position: Vec2<T>,
+    pub dimensions: Vec2<T>,
+}
+
 num_trait_impl!();
 #[cfg(feature = "simd")]
 num_trait_simd_impl!();
@@ -160,3 +181,25 @@ impl<T: Copy + One + Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<O
         }
     }
 }
+
+impl<T: Copy + Ord + Add<Output = T> + Sub<Output = T>> Rect<T> {
+    /// Returns the area covered by both rectangles, or `None` if they don't overlap.
+    pub fn intersect(self, other: Self) -> Option<Self> {
+        let start = Vec2 {
+            x: self.position.x.max(other.position.x),
+            y: self.position.y.max(other.position.y),
+        };
+        let end = Vec2 {
+            x: (self.position.x + self.dimensions.x).min(other.position.x + other.dimensions.x),
+            y: (self.position.y + self.dimensions.y).min(other.position.y + other.dimensions.y),
+        };
+
+        (start.x < end.x && start.y < end.y).then(|| Self {
+            position: start,
+            dimensions: Vec2 {
+                x: end.x - start.x,
+                y: end.y - start.y,
+            },
+        })
+    }
+}
@@ -1,24 +1,366 @@
+use std::collections::HashMap;
+#[cfg(feature = "simd")]
+use std::{
+    array,
+    simd::{LaneCount, SupportedLaneCount},
+};
+
 use crate::{
-    math::Vec2,
-    raster::{Rasterizer, Tile},
+    raster::{PixelResult, RasterVertex, Rasterizer, Statistics, Tile},
     shader::{PixelShader, VertexShader},
 };
 
+#[cfg(feature = "simd")]
+use crate::shader::{Lanes, SimdVertexShader};
+
+use state::{PipelineState, Topology};
+use vertex::{FetchVertex, FetchedVertex, IndexBuffer, VertexBuffer, VertexFetch};
+
+pub mod command;
+pub mod state;
+pub mod vertex;
+
 pub trait VertexState {
     type Vertex;
 
     fn get_vertex_index(&self) -> usize;
+    /// Returns the index of the instance being drawn, `0` outside of instanced draws.
+    fn get_instance_index(&self) -> usize;
     fn get_vertex(&self) -> Self::Vertex;
 }
 
 pub trait PixelState {
     type Pixel;
+    type FragCoord;
+    type Barycentric;
 
     fn get_pixel(&self) -> Self::Pixel;
+    /// Returns the coordinates of the pixel being shaded along with its depth.
+    fn get_frag_coord(&self) -> Self::FragCoord;
+    /// Returns the weight of each vertex of the primitive at the pixel being shaded.
+    fn get_barycentric(&self) -> Self::Barycentric;
+    /// Returns the index of the primitive being shaded in the list passed to the rasterizer.
+    fn get_primitive_index(&self) -> usize;
+    /// Returns the index of the instance the primitive belongs to, `0` outside of instanced
+    /// draws.
+    fn get_instance_index(&self) -> usize;
+    fn get_front_facing(&self) -> bool;
+    /// Returns the index of the sample being shaded within the pixel.
+    fn get_sample_index(&self) -> usize;
+}
+
+/// Rasterizer drawing vertex buffers through a [PipelineState], implemented for every
+/// rasterizer.
+///
+/// Vertex inputs of the pipeline's shaders are built from the [FetchedVertex] of every vertex
+/// with attributes `A`, and pixel inputs from the state of every pixel. Draws go to the `M`
+/// color targets of a tile, pixel shaders returning one color per target.
+pub trait Pipeline<'a, T>: Rasterizer<'a, T> {
+    /// Draws the first `count` vertices of `buffers`, laid out as described by `pipeline`.
+    fn render<S, A, const M: usize>(
+        &mut self,
+        pipeline: &PipelineState<S>,
+        tile: Tile<'_, M>,
+        buffers: &[&dyn VertexBuffer],
+        count: usize,
+    ) where
+        S: VertexShader<T> + PixelShader<T>,
+        A: FetchVertex,
+        for<'b> S::VertexInput: From<FetchedVertex<'b, A>>,
+        S::VertexOutput: RasterVertex<T>,
+        for<'s> S::PixelInput: From<&'s Self::State>,
+        S::PixelOutput: PixelResult<[Self::Color; M], Self::Mask>,
+    {
+        self.draw_instanced::<S, A, M>(pipeline, tile, buffers, count, 1);
+    }
+
+    /// Draws `instances` instances of the first `count` vertices of `buffers`, per-instance
+    /// attributes advancing with the instance index.
+    ///
+    /// Binds the rasterizer state of `pipeline`, which stays bound afterwards. Every instance
+    /// is rasterized separately, primitive indices starting over at `0` and pixel states
+    /// telling instances apart by their instance index.
+    fn draw_instanced<S, A, const M: usize>(
+        &mut self,
+        pipeline: &PipelineState<S>,
+        tile: Tile<'_, M>,
+        buffers: &[&dyn VertexBuffer],
+        count: usize,
+        instances: usize,
+    ) where
+        S: VertexShader<T> + PixelShader<T>,
+        A: FetchVertex,
+        for<'b> S::VertexInput: From<FetchedVertex<'b, A>>,
+        S::VertexOutput: RasterVertex<T>,
+        for<'s> S::PixelInput: From<&'s Self::State>,
+        S::PixelOutput: PixelResult<[Self::Color; M], Self::Mask>,
+    {
+        let shader = pipeline.shader();
+        let vertices = VertexFetch::new(pipeline.layout(), buffers);
+
+        draw_instances(
+            self,
+            pipeline,
+            tile,
+            instances,
+            |instance, shaded, statistics| {
+                shaded.extend(
+                    (0..count)
+                        .map(|index| shader.vertex(vertices.vertex::<A>(index, instance).into())),
+                );
+                statistics.vertices_shaded += count as u64;
+            },
+        );
+    }
+
+    /// Draws the vertices of `buffers` at `indices`, laid out as described by `pipeline`.
+    fn draw_indexed<S, A, const M: usize>(
+        &mut self,
+        pipeline: &PipelineState<S>,
+        tile: Tile<'_, M>,
+        buffers: &[&dyn VertexBuffer],
+        indices: IndexBuffer<'_>,
+    ) where
+        S: VertexShader<T> + PixelShader<T>,
+        A: FetchVertex,
+        for<'b> S::VertexInput: From<FetchedVertex<'b, A>>,
+        S::VertexOutput: RasterVertex<T>,
+        for<'s> S::PixelInput: From<&'s Self::State>,
+        S::PixelOutput: PixelResult<[Self::Color; M], Self::Mask>,
+    {
+        self.draw_indexed_instanced::<S, A, M>(pipeline, tile, buffers, indices, 1);
+    }
+
+    /// Draws `instances` instances of the vertices of `buffers` at `indices`.
+    ///
+    /// Indices are deduplicated once for the whole draw, so that vertices referenced by several
+    /// of them only get shaded once per instance, the repeated indices being counted as hits of
+    /// a post-transform cache in the statistics.
+    fn draw_indexed_instanced<S, A, const M: usize>(
+        &mut self,
+        pipeline: &PipelineState<S>,
+        tile: Tile<'_, M>,
+        buffers: &[&dyn VertexBuffer],
+        indices: IndexBuffer<'_>,
+        instances: usize,
+    ) where
+        S: VertexShader<T> + PixelShader<T>,
+        A: FetchVertex,
+        for<'b> S::VertexInput: From<FetchedVertex<'b, A>>,
+        S::VertexOutput: RasterVertex<T>,
+        for<'s> S::PixelInput: From<&'s Self::State>,
+        S::PixelOutput: PixelResult<[Self::Color; M], Self::Mask>,
+    {
+        let shader = pipeline.shader();
+        let vertices = VertexFetch::new(pipeline.layout(), buffers);
+        let (distinct, positions) = deduplicate(indices);
+        let mut outputs = Vec::with_capacity(distinct.len());
+
+        draw_instances(
+            self,
+            pipeline,
+            tile,
+            instances,
+            |instance, shaded, statistics| {
+                outputs.clear();
+                outputs.extend(
+                    distinct
+                        .iter()
+                        .map(|&index| shader.vertex(vertices.vertex::<A>(index, instance).into())),
+                );
+                shaded.extend(positions.iter().map(|&position| outputs[position]));
+                count_cache(statistics, indices.len(), distinct.len());
+            },
+        );
+    }
+
+    /// Draws like [Pipeline::draw_instanced], running the vertex shader on `N` vertices at
+    /// once.
+    #[cfg(feature = "simd")]
+    fn draw_instanced_simd<S, A, const N: usize, const M: usize>(
+        &mut self,
+        pipeline: &PipelineState<S>,
+        tile: Tile<'_, M>,
+        buffers: &[&dyn VertexBuffer],
+        count: usize,
+        instances: usize,
+    ) where
+        LaneCount<N>: SupportedLaneCount,
+        S: SimdVertexShader<T, N> + PixelShader<T>,
+        A: FetchVertex,
+        for<'b> <S::VertexInput as Lanes<N>>::Lane: From<FetchedVertex<'b, A>>,
+        <S::VertexOutput as Lanes<N>>::Lane: RasterVertex<T>,
+        for<'s> S::PixelInput: From<&'s Self::State>,
+        S::PixelOutput: PixelResult<[Self::Color; M], Self::Mask>,
+    {
+        let shader = pipeline.shader();
+        let vertices = VertexFetch::new(pipeline.layout(), buffers);
+        let indices = (0..count).collect::<Vec<_>>();
+
+        draw_instances(
+            self,
+            pipeline,
+            tile,
+            instances,
+            |instance, shaded, statistics| {
+                shade_simd::<T, S, A, N>(shader, vertices, &indices, instance, shaded);
+                statistics.vertices_shaded += count as u64;
+            },
+        );
+    }
+
+    /// Draws like [Pipeline::draw_indexed_instanced], running the vertex shader on `N`
+    /// vertices at once.
+    ///
+    /// Indices are deduplicated once for the whole draw, and only distinct vertices get
+    /// batched and shaded.
+    #[cfg(feature = "simd")]
+    fn draw_indexed_instanced_simd<S, A, const N: usize, const M: usize>(
+        &mut self,
+        pipeline: &PipelineState<S>,
+        tile: Tile<'_, M>,
+        buffers: &[&dyn VertexBuffer],
+        indices: IndexBuffer<'_>,
+        instances: usize,
+    ) where
+        LaneCount<N>: SupportedLaneCount,
+        S: SimdVertexShader<T, N> + PixelShader<T>,
+        A: FetchVertex,
+        for<'b> <S::VertexInput as Lanes<N>>::Lane: From<FetchedVertex<'b, A>>,
+        <S::VertexOutput as Lanes<N>>::Lane: RasterVertex<T>,
+        for<'s> S::PixelInput: From<&'s Self::State>,
+        S::PixelOutput: PixelResult<[Self::Color; M], Self::Mask>,
+    {
+        let shader = pipeline.shader();
+        let vertices = VertexFetch::new(pipeline.layout(), buffers);
+        let (distinct, positions) = deduplicate(indices);
+        let mut outputs = Vec::with_capacity(distinct.len());
+
+        draw_instances(
+            self,
+            pipeline,
+            tile,
+            instances,
+            |instance, shaded, statistics| {
+                outputs.clear();
+                shade_simd::<T, S, A, N>(shader, vertices, &distinct, instance, &mut outputs);
+                shaded.extend(positions.iter().map(|&position| outputs[position]));
+                count_cache(statistics, indices.len(), distinct.len());
+            },
+        );
+    }
 }
 
-pub trait Pipeline<'a, T>: Rasterizer<'a, T> + VertexShader<T> + PixelShader<T> {
-    fn render(&mut self, _tile: Tile<'a>, mut _list: &'a [Vec2<T>]) {
-        // self.rasterize(tile, list, pixel)
+impl<'a, T, R: Rasterizer<'a, T>> Pipeline<'a, T> for R {}
+
+/// Binds the rasterizer state of `pipeline` and rasterizes `instances` instances of the vertices
+/// `shade` appends for every instance.
+fn draw_instances<'a, T, P, S, V, const M: usize>(
+    rasterizer: &mut P,
+    pipeline: &PipelineState<S>,
+    mut tile: Tile<'_, M>,
+    instances: usize,
+    mut shade: impl FnMut(usize, &mut Vec<V>, &mut Statistics),
+) where
+    P: Rasterizer<'a, T> + ?Sized,
+    S: PixelShader<T>,
+    V: RasterVertex<T>,
+    for<'s> S::PixelInput: From<&'s P::State>,
+    S::PixelOutput: PixelResult<[P::Color; M], P::Mask>,
+{
+    let shader = pipeline.shader();
+    let mut shaded = Vec::new();
+    let mut list = Vec::new();
+
+    *rasterizer.state_mut() = *pipeline.state();
+
+    for instance in 0..instances {
+        shaded.clear();
+        shade(instance, &mut shaded, rasterizer.statistics_mut());
+
+        let list = assemble(pipeline.topology(), &shaded, &mut list);
+
+        rasterizer.rasterize_instance(tile.reborrow(), list, instance, |state| {
+            shader.pixel(state.into())
+        });
+    }
+}
+
+/// Counts the indices of a draw that referenced an already shaded vertex as hits of the
+/// post-transform cache, and the `distinct` vertices shaded as misses.
+fn count_cache(statistics: &mut Statistics, indices: usize, distinct: usize) {
+    let misses = distinct as u64;
+
+    statistics.vertices_shaded += misses;
+    statistics.vertex_cache_hits += indices as u64 - misses;
+    statistics.vertex_cache_misses += misses;
+}
+
+/// Returns the triangle list made of `vertices` according to `topology`, assembled into `list`
+/// if they don't already make one.
+fn assemble<'v, V: Copy>(topology: Topology, vertices: &'v [V], list: &'v mut Vec<V>) -> &'v [V] {
+    match topology {
+        Topology::TriangleList => vertices,
+        Topology::TriangleStrip => {
+            list.clear();
+
+            for (i, triangle) in vertices.windows(3).enumerate() {
+                // every other triangle of a strip is wound the other way
+                match i % 2 {
+                    0 => list.extend_from_slice(triangle),
+                    _ => list.extend([triangle[1], triangle[0], triangle[2]]),
+                }
+            }
+
+            list
+        }
+    }
+}
+
+/// Returns the distinct indices of `indices` in the order they first appear, along with the
+/// position of every index among them.
+fn deduplicate(indices: IndexBuffer<'_>) -> (Vec<usize>, Vec<usize>) {
+    let mut slots = HashMap::with_capacity(indices.len());
+    let mut distinct = Vec::new();
+    let positions = indices
+        .iter()
+        .map(|index| {
+            *slots.entry(index).or_insert_with(|| {
+                distinct.push(index);
+                distinct.len() - 1
+            })
+        })
+        .collect();
+
+    (distinct, positions)
+}
+
+/// Shades the vertices at `indices` of instance `instance` with `shader` `N` at a time,
+/// appending them to `shaded`.
+///
+/// The last batch is padded with its last vertex, whose copies are dropped.
+#[cfg(feature = "simd")]
+fn shade_simd<T, S, A, const N: usize>(
+    shader: &S,
+    vertices: VertexFetch<'_>,
+    indices: &[usize],
+    instance: usize,
+    shaded: &mut Vec<<S::VertexOutput as Lanes<N>>::Lane>,
+) where
+    LaneCount<N>: SupportedLaneCount,
+    S: SimdVertexShader<T, N>,
+    A: FetchVertex,
+    for<'b> <S::VertexInput as Lanes<N>>::Lane: From<FetchedVertex<'b, A>>,
+    <S::VertexOutput as Lanes<N>>::Lane: Copy,
+{
+    for batch in indices.chunks(N) {
+        let input = array::from_fn(|lane| {
+            let index = batch[lane.min(batch.len() - 1)];
+
+            vertices.vertex::<A>(index, instance).into()
+        });
+        let output = shader.vertex(S::VertexInput::from_lanes(input)).to_lanes();
+
+        shaded.extend_from_slice(&output[..batch.len()]);
     }
 }
@@ -0,0 +1,222 @@
+//! Recording of draws and the state they depend on into lists replayed later.
+//!
+//! A [CommandList] only borrows the pipeline states and vertex buffers it draws with, so it can
+//! be recorded on one thread while another one is still drawing, then replayed through a
+//! [Pipeline] any number of times and to any set of tiles. Draws are type-erased, so that a
+//! single list can hold the draws of a whole frame whatever their shaders and vertices, as long
+//! as they go through the same rasterizer.
+
+use std::{fmt, marker::PhantomData, sync::Arc};
+
+use crate::{
+    raster::{PixelResult, RasterVertex, Tile},
+    shader::{PixelShader, VertexShader},
+};
+
+use super::{
+    state::PipelineState,
+    vertex::{FetchVertex, FetchedVertex, IndexBuffer, VertexBuffer},
+    Pipeline,
+};
+
+/// Draw recorded with its pipeline state and vertex buffers, replayed through rasterizer `P` to
+/// a tile.
+pub type Draw<'a, P, const M: usize> = Arc<dyn Fn(&mut P, Tile<'_, M>) + Send + Sync + 'a>;
+
+pub enum Command<'a, P, const M: usize> {
+    /// Makes the following commands apply to the tile at the given index of those passed to
+    /// [CommandList::replay], the first one being bound at first.
+    BindTile(usize),
+    /// Fills the color targets of the bound tile with a color.
+    Clear(u32),
+    /// Fills the depth target of the bound tile with a depth.
+    ClearDepth(f32),
+    /// Draws to the bound tile, binding the rasterizer state of the draw's pipeline state.
+    Draw(Draw<'a, P, M>),
+}
+
+/// Commands drawing through rasterizer `P` to tiles with `M` color targets, applied in the
+/// order they were recorded.
+pub struct CommandList<'a, T, P, const M: usize = 1> {
+    commands: Vec<Command<'a, P, M>>,
+    rasterizer: PhantomData<fn() -> T>,
+}
+
+impl<'a, T, P, const M: usize> CommandList<'a, T, P, M> {
+    pub fn new() -> Self {
+        Self {
+            commands: Vec::new(),
+            rasterizer: PhantomData,
+        }
+    }
+
+    pub fn commands(&self) -> &[Command<'a, P, M>] {
+        &self.commands
+    }
+
+    /// Removes every command, keeping the allocated memory to record the next ones.
+    pub fn reset(&mut self) {
+        self.commands.clear();
+    }
+
+    pub fn push(&mut self, command: Command<'a, P, M>) {
+        self.commands.push(command);
+    }
+
+    pub fn bind_tile(&mut self, index: usize) {
+        self.push(Command::BindTile(index));
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        self.push(Command::Clear(color));
+    }
+
+    pub fn clear_depth(&mut self, depth: f32) {
+        self.push(Command::ClearDepth(depth));
+    }
+
+    /// Records a draw of the first `count` vertices of `buffers`, as [Pipeline::render] does.
+    pub fn draw<S, A>(
+        &mut self,
+        pipeline: &'a PipelineState<S>,
+        buffers: &'a [&'a dyn VertexBuffer],
+        count: usize,
+    ) where
+        P: Pipeline<'a, T>,
+        S: VertexShader<T> + PixelShader<T> + Sync,
+        A: FetchVertex,
+        for<'b> S::VertexInput: From<FetchedVertex<'b, A>>,
+        S::VertexOutput: RasterVertex<T>,
+        for<'s> S::PixelInput: From<&'s P::State>,
+        S::PixelOutput: PixelResult<[P::Color; M], P::Mask>,
+    {
+        self.draw_instanced::<S, A>(pipeline, buffers, count, 1);
+    }
+
+    /// Records a draw of instances of the first `count` vertices of `buffers`, as
+    /// [Pipeline::draw_instanced] does.
+    pub fn draw_instanced<S, A>(
+        &mut self,
+        pipeline: &'a PipelineState<S>,
+        buffers: &'a [&'a dyn VertexBuffer],
+        count: usize,
+        instances: usize,
+    ) where
+        P: Pipeline<'a, T>,
+        S: VertexShader<T> + PixelShader<T> + Sync,
+        A: FetchVertex,
+        for<'b> S::VertexInput: From<FetchedVertex<'b, A>>,
+        S::VertexOutput: RasterVertex<T>,
+        for<'s> S::PixelInput: From<&'s P::State>,
+        S::PixelOutput: PixelResult<[P::Color; M], P::Mask>,
+    {
+        self.push(Command::Draw(Arc::new(move |rasterizer: &mut P, tile| {
+            rasterizer.draw_instanced::<S, A, M>(pipeline, tile, buffers, count, instances);
+        })));
+    }
+
+    /// Records a draw of the vertices of `buffers` at `indices`, as [Pipeline::draw_indexed]
+    /// does.
+    pub fn draw_indexed<S, A>(
+        &mut self,
+        pipeline: &'a PipelineState<S>,
+        buffers: &'a [&'a dyn VertexBuffer],
+        indices: IndexBuffer<'a>,
+    ) where
+        P: Pipeline<'a, T>,
+        S: VertexShader<T> + PixelShader<T> + Sync,
+        A: FetchVertex,
+        for<'b> S::VertexInput: From<FetchedVertex<'b, A>>,
+        S::VertexOutput: RasterVertex<T>,
+        for<'s> S::PixelInput: From<&'s P::State>,
+        S::PixelOutput: PixelResult<[P::Color; M], P::Mask>,
+    {
+        self.draw_indexed_instanced::<S, A>(pipeline, buffers, indices, 1);
+    }
+
+    /// Records a draw of instances of the vertices of `buffers` at `indices`, as
+    /// [Pipeline::draw_indexed_instanced] does.
+    pub fn draw_indexed_instanced<S, A>(
+        &mut self,
+        pipeline: &'a PipelineState<S>,
+        buffers: &'a [&'a dyn VertexBuffer],
+        indices: IndexBuffer<'a>,
+        instances: usize,
+    ) where
+        P: Pipeline<'a, T>,
+        S: VertexShader<T> + PixelShader<T> + Sync,
+        A: FetchVertex,
+        for<'b> S::VertexInput: From<FetchedVertex<'b, A>>,
+        S::VertexOutput: RasterVertex<T>,
+        for<'s> S::PixelInput: From<&'s P::State>,
+        S::PixelOutput: PixelResult<[P::Color; M], P::Mask>,
+    {
+        self.push(Command::Draw(Arc::new(move |rasterizer: &mut P, tile| {
+            rasterizer
+                .draw_indexed_instanced::<S, A, M>(pipeline, tile, buffers, indices, instances);
+        })));
+    }
+
+    /// Applies the commands through `rasterizer` to `tiles`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a command applies to a tile out of bounds.
+    pub fn replay(&self, rasterizer: &mut P, tiles: &mut [Tile<'_, M>]) {
+        let mut tile = 0;
+
+        for command in &self.commands {
+            match command {
+                Command::BindTile(index) => tile = *index,
+                Command::Clear(color) => tiles[tile].clear(*color),
+                Command::ClearDepth(depth) => tiles[tile].clear_depth(*depth),
+                Command::Draw(draw) => draw(rasterizer, tiles[tile].reborrow()),
+            }
+        }
+    }
+}
+
+impl<P, const M: usize> fmt::Debug for Command<'_, P, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BindTile(index) => f.debug_tuple("BindTile").field(index).finish(),
+            Self::Clear(color) => f.debug_tuple("Clear").field(color).finish(),
+            Self::ClearDepth(depth) => f.debug_tuple("ClearDepth").field(depth).finish(),
+            Self::Draw(_) => f.debug_tuple("Draw").finish_non_exhaustive(),
+        }
+    }
+}
+
+impl<P, const M: usize> Clone for Command<'_, P, M> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::BindTile(index) => Self::BindTile(*index),
+            Self::Clear(color) => Self::Clear(*color),
+            Self::ClearDepth(depth) => Self::ClearDepth(*depth),
+            Self::Draw(draw) => Self::Draw(draw.clone()),
+        }
+    }
+}
+
+impl<T, P, const M: usize> fmt::Debug for CommandList<'_, T, P, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandList")
+            .field("commands", &self.commands)
+            .finish()
+    }
+}
+
+impl<T, P, const M: usize> Clone for CommandList<'_, T, P, M> {
+    fn clone(&self) -> Self {
+        Self {
+            commands: self.commands.clone(),
+            rasterizer: PhantomData,
+        }
+    }
+}
+
+impl<T, P, const M: usize> Default for CommandList<'_, T, P, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,204 @@
+//! Pipeline state objects, bundling everything draws depend on apart from their vertex buffers
+//! and targets.
+//!
+//! A [PipelineState] is validated once when built by its [PipelineStateBuilder], and is then
+//! only borrowed by the draws using it.
+
+use std::{error::Error, fmt};
+
+use crate::{
+    math::Rect,
+    raster::{BlendMode, Conservative, CullMode, DepthState, FillMode, RasterizerState},
+};
+
+use super::vertex::{StepMode, VertexLayout};
+
+/// How vertices are assembled into triangles.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    /// Every three vertices make a triangle.
+    #[default]
+    TriangleList,
+    /// Every vertex makes a triangle with the two previous ones, keeping the winding of the
+    /// first triangle.
+    TriangleStrip,
+}
+
+/// Vertex layout, topology, rasterizer state and shader pair `S` of a draw.
+#[derive(Debug, Clone)]
+pub struct PipelineState<S> {
+    layout: VertexLayout,
+    topology: Topology,
+    state: RasterizerState,
+    shader: S,
+}
+
+#[derive(Debug, Clone)]
+pub struct PipelineStateBuilder<S> {
+    layout: VertexLayout,
+    topology: Topology,
+    state: RasterizerState,
+    shader: S,
+}
+
+/// Reason a [PipelineStateBuilder] failed to build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineStateError {
+    /// An attribute reads from a buffer missing from the layout.
+    MissingBuffer { attribute: usize },
+    /// An attribute has no components or more than 4.
+    InvalidComponents { attribute: usize },
+    /// An attribute extends past the stride of its buffer.
+    AttributeOverflow { attribute: usize },
+    /// A buffer advances once every zero instances.
+    ZeroStepRate { buffer: usize },
+}
+
+impl<S> PipelineState<S> {
+    pub fn builder(shader: S) -> PipelineStateBuilder<S> {
+        PipelineStateBuilder::new(shader)
+    }
+
+    pub fn layout(&self) -> &VertexLayout {
+        &self.layout
+    }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn state(&self) -> &RasterizerState {
+        &self.state
+    }
+
+    pub fn shader(&self) -> &S {
+        &self.shader
+    }
+
+    /// Returns the shader pair, whose uniforms may be changed between draws without building
+    /// the pipeline state again.
+    pub fn shader_mut(&mut self) -> &mut S {
+        &mut self.shader
+    }
+}
+
+impl<S> PipelineStateBuilder<S> {
+    /// Creates a builder of a pipeline state drawing triangle lists with the default
+    /// rasterizer state and no vertex attributes.
+    pub fn new(shader: S) -> Self {
+        Self {
+            layout: VertexLayout::default(),
+            topology: Topology::default(),
+            state: RasterizerState::default(),
+            shader,
+        }
+    }
+
+    pub fn layout(mut self, layout: VertexLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn topology(mut self, topology: Topology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    /// Sets the whole rasterizer state at once, replacing the parts set so far.
+    pub fn state(mut self, state: RasterizerState) -> Self {
+        self.state = state;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: CullMode) -> Self {
+        self.state.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn fill_mode(mut self, fill_mode: FillMode) -> Self {
+        self.state.fill_mode = fill_mode;
+        self
+    }
+
+    pub fn depth(mut self, depth: DepthState) -> Self {
+        self.state.depth = depth;
+        self
+    }
+
+    pub fn blend(mut self, blend: BlendMode) -> Self {
+        self.state.blend = blend;
+        self
+    }
+
+    pub fn scissor(mut self, scissor: Rect<usize>) -> Self {
+        self.state.scissor = Some(scissor);
+        self
+    }
+
+    pub fn conservative(mut self, conservative: Conservative) -> Self {
+        self.state.conservative = conservative;
+        self
+    }
+
+    pub fn antialias(mut self, antialias: bool) -> Self {
+        self.state.antialias = antialias;
+        self
+    }
+
+    /// Validates the vertex layout and builds the pipeline state.
+    pub fn build(self) -> Result<PipelineState<S>, PipelineStateError> {
+        for (buffer, layout) in self.layout.buffers.iter().enumerate() {
+            if layout.step == StepMode::Instance(0) {
+                return Err(PipelineStateError::ZeroStepRate { buffer });
+            }
+        }
+
+        for (index, attribute) in self.layout.attributes.iter().enumerate() {
+            let Some(buffer) = self.layout.buffers.get(attribute.buffer) else {
+                return Err(PipelineStateError::MissingBuffer { attribute: index });
+            };
+
+            if !(1..=4).contains(&attribute.components) {
+                return Err(PipelineStateError::InvalidComponents { attribute: index });
+            }
+
+            // a stride of zero repeats the same element for every vertex
+            let end = attribute.offset + attribute.components * attribute.format.size();
+
+            if buffer.stride != 0 && end > buffer.stride {
+                return Err(PipelineStateError::AttributeOverflow { attribute: index });
+            }
+        }
+
+        Ok(PipelineState {
+            layout: self.layout,
+            topology: self.topology,
+            state: self.state,
+            shader: self.shader,
+        })
+    }
+}
+
+impl fmt::Display for PipelineStateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingBuffer { attribute } => {
+                write!(f, "attribute {attribute} reads from a missing buffer")
+            }
+            Self::InvalidComponents { attribute } => {
+                write!(f, "attribute {attribute} doesn't have 1 to 4 components")
+            }
+            Self::AttributeOverflow { attribute } => {
+                write!(
+                    f,
+                    "attribute {attribute} extends past the stride of its buffer"
+                )
+            }
+            Self::ZeroStepRate { buffer } => {
+                write!(f, "buffer {buffer} advances once every zero instances")
+            }
+        }
+    }
+}
+
+impl Error for PipelineStateError {}
@@ -0,0 +1,384 @@
+//! Vertex input layouts and attribute fetch from byte buffers.
+//!
+//! A [VertexLayout] describes where the attributes of a vertex are stored in a set of
+//! interleaved or separate byte slices. [VertexFetch] decodes them in layout order into the
+//! [VertexShader::VertexInput](crate::shader::VertexShader::VertexInput) of a shader, which
+//! describes how to do so by implementing [FetchVertex].
+//!
+//! Vertices are read from any [VertexBuffer], such as byte vectors or the [BufferGuard] of a
+//! locked [LockBuffer](crate::buffer::LockBuffer), which stays locked while drawing.
+
+use std::{fmt, marker::PhantomData};
+
+use crate::{
+    buffer::BufferGuard,
+    math::{Vec2, Vec3, Vec4},
+    NumberCast,
+};
+
+use super::VertexState;
+
+macro_rules! attribute_impl {
+    ($t:tt) => {
+        impl Attribute for $t {
+            #[inline(always)]
+            fn from_components(components: [f64; 4]) -> Self {
+                components[0].to_num()
+            }
+        }
+
+        impl Attribute for Vec2<$t> {
+            #[inline(always)]
+            fn from_components([x, y, ..]: [f64; 4]) -> Self {
+                Vec2 {
+                    x: x.to_num(),
+                    y: y.to_num(),
+                }
+            }
+        }
+
+        impl Attribute for Vec3<$t> {
+            #[inline(always)]
+            fn from_components([x, y, z, _]: [f64; 4]) -> Self {
+                Vec3 {
+                    x: x.to_num(),
+                    y: y.to_num(),
+                    z: z.to_num(),
+                }
+            }
+        }
+
+        impl Attribute for Vec4<$t> {
+            #[inline(always)]
+            fn from_components([x, y, z, w]: [f64; 4]) -> Self {
+                Vec4 {
+                    x: x.to_num(),
+                    y: y.to_num(),
+                    z: z.to_num(),
+                    w: w.to_num(),
+                }
+            }
+        }
+    };
+
+    () => {
+        attribute_impl!(i8);
+        attribute_impl!(i16);
+        attribute_impl!(i32);
+        attribute_impl!(i64);
+        attribute_impl!(isize);
+        attribute_impl!(u8);
+        attribute_impl!(u16);
+        attribute_impl!(u32);
+        attribute_impl!(u64);
+        attribute_impl!(usize);
+        attribute_impl!(f32);
+        attribute_impl!(f64);
+    };
+}
+
+/// Storage format of the components of a vertex attribute, all of them little-endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    F32,
+    /// IEEE 754 half-precision float.
+    F16,
+    U32,
+    I32,
+    U16,
+    I16,
+    U8,
+    I8,
+    /// Unsigned integer mapped to `[0, 1]`.
+    U16Norm,
+    /// Signed integer mapped to `[-1, 1]`.
+    I16Norm,
+    U8Norm,
+    I8Norm,
+}
+
+/// How the element of a vertex buffer an attribute is read from advances.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StepMode {
+    /// Advances once per vertex.
+    #[default]
+    Vertex,
+    /// Advances once every given number of instances, which must not be zero.
+    Instance(usize),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct VertexBufferLayout {
+    /// Distance in bytes between consecutive elements.
+    pub stride: usize,
+    pub step: StepMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VertexAttribute {
+    /// Index of the buffer the attribute is read from.
+    pub buffer: usize,
+    /// Offset in bytes of the attribute from the start of an element.
+    pub offset: usize,
+    pub format: Format,
+    /// Number of components, from 1 to 4.
+    pub components: usize,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct VertexLayout {
+    pub buffers: Vec<VertexBufferLayout>,
+    /// Attributes in the order they are read by [FetchVertex::fetch].
+    pub attributes: Vec<VertexAttribute>,
+}
+
+/// Indices of the vertices drawn by an indexed draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexBuffer<'a> {
+    U16(&'a [u16]),
+    U32(&'a [u32]),
+}
+
+/// Value of a vertex attribute built from its decoded components.
+pub trait Attribute {
+    /// Builds the attribute from its components, missing ones being `0` apart from the fourth
+    /// defaulting to `1`.
+    fn from_components(components: [f64; 4]) -> Self;
+}
+
+/// Vertex decoded from the attributes of a [VertexLayout].
+pub trait FetchVertex {
+    fn fetch(attributes: &mut Attributes<'_>) -> Self;
+}
+
+/// Bytes of a vertex buffer that attributes are read from.
+///
+/// Buffers have to be [Sync], so that command lists borrowing them can be recorded and replayed
+/// on different threads.
+pub trait VertexBuffer: Sync {
+    fn bytes(&self) -> &[u8];
+}
+
+/// Decodes vertices described by a [VertexLayout] from vertex buffers.
+#[derive(Debug, Clone, Copy)]
+pub struct VertexFetch<'a> {
+    pub layout: &'a VertexLayout,
+    /// Buffers indexed by [VertexAttribute::buffer].
+    pub buffers: &'a [&'a dyn VertexBuffer],
+}
+
+/// Attributes of a single vertex, read in layout order.
+#[derive(Debug)]
+pub struct Attributes<'a> {
+    fetch: VertexFetch<'a>,
+    vertex: usize,
+    instance: usize,
+    next: usize,
+}
+
+/// [VertexState] of a vertex decoded on demand by a [VertexFetch].
+#[derive(Debug)]
+pub struct FetchedVertex<'a, V> {
+    pub fetch: VertexFetch<'a>,
+    pub vertex: usize,
+    pub instance: usize,
+    _marker: PhantomData<V>,
+}
+
+attribute_impl!();
+
+impl Format {
+    /// Size in bytes of a single component.
+    pub fn size(self) -> usize {
+        match self {
+            Self::F32 | Self::U32 | Self::I32 => 4,
+            Self::F16 | Self::U16 | Self::I16 | Self::U16Norm | Self::I16Norm => 2,
+            Self::U8 | Self::I8 | Self::U8Norm | Self::I8Norm => 1,
+        }
+    }
+
+    #[inline(always)]
+    fn decode(self, bytes: &[u8]) -> f64 {
+        let bytes2 = || [bytes[0], bytes[1]];
+        let bytes4 = || [bytes[0], bytes[1], bytes[2], bytes[3]];
+
+        match self {
+            Self::F32 => f32::from_le_bytes(bytes4()) as f64,
+            Self::F16 => half(u16::from_le_bytes(bytes2())),
+            Self::U32 => u32::from_le_bytes(bytes4()) as f64,
+            Self::I32 => i32::from_le_bytes(bytes4()) as f64,
+            Self::U16 => u16::from_le_bytes(bytes2()) as f64,
+            Self::I16 => i16::from_le_bytes(bytes2()) as f64,
+            Self::U8 => bytes[0] as f64,
+            Self::I8 => bytes[0] as i8 as f64,
+            Self::U16Norm => u16::from_le_bytes(bytes2()) as f64 / u16::MAX as f64,
+            Self::I16Norm => (i16::from_le_bytes(bytes2()) as f64 / i16::MAX as f64).max(-1.0),
+            Self::U8Norm => bytes[0] as f64 / u8::MAX as f64,
+            Self::I8Norm => (bytes[0] as i8 as f64 / i8::MAX as f64).max(-1.0),
+        }
+    }
+}
+
+impl IndexBuffer<'_> {
+    pub fn len(&self) -> usize {
+        match self {
+            Self::U16(indices) => indices.len(),
+            Self::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    #[inline(always)]
+    pub fn get(&self, i: usize) -> usize {
+        match self {
+            Self::U16(indices) => indices[i] as usize,
+            Self::U32(indices) => indices[i] as usize,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+}
+
+impl VertexBuffer for [u8] {
+    #[inline(always)]
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<const N: usize> VertexBuffer for [u8; N] {
+    #[inline(always)]
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl VertexBuffer for Vec<u8> {
+    #[inline(always)]
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl VertexBuffer for &[u8] {
+    #[inline(always)]
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<F: FnOnce() + Sync> VertexBuffer for BufferGuard<'_, u8, F> {
+    #[inline(always)]
+    fn bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Debug for dyn VertexBuffer + '_ {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.bytes().fmt(f)
+    }
+}
+
+impl<'a> VertexFetch<'a> {
+    pub fn new(layout: &'a VertexLayout, buffers: &'a [&'a dyn VertexBuffer]) -> Self {
+        Self { layout, buffers }
+    }
+
+    /// Decodes vertex `vertex` of instance `instance`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an attribute lies outside of its buffer.
+    #[inline(always)]
+    pub fn fetch<V: FetchVertex>(&self, vertex: usize, instance: usize) -> V {
+        V::fetch(&mut Attributes {
+            fetch: *self,
+            vertex,
+            instance,
+            next: 0,
+        })
+    }
+
+    /// Returns the [VertexState] of vertex `vertex` of instance `instance`, decoding it lazily.
+    pub fn vertex<V: FetchVertex>(&self, vertex: usize, instance: usize) -> FetchedVertex<'a, V> {
+        FetchedVertex {
+            fetch: *self,
+            vertex,
+            instance,
+            _marker: PhantomData,
+        }
+    }
+
+    #[inline(always)]
+    fn components(&self, attribute: &VertexAttribute, vertex: usize, instance: usize) -> [f64; 4] {
+        let layout = self.layout.buffers[attribute.buffer];
+        let element = match layout.step {
+            StepMode::Vertex => vertex,
+            StepMode::Instance(rate) => instance / rate,
+        };
+        let size = attribute.format.size();
+        let start = element * layout.stride + attribute.offset;
+        let bytes =
+            &self.buffers[attribute.buffer].bytes()[start..start + attribute.components * size];
+        let mut components = [0.0, 0.0, 0.0, 1.0];
+
+        for (component, bytes) in components.iter_mut().zip(bytes.chunks_exact(size)) {
+            *component = attribute.format.decode(bytes);
+        }
+
+        components
+    }
+}
+
+impl Attributes<'_> {
+    /// Decodes the next attribute of the layout.
+    ///
+    /// # Panics
+    ///
+    /// Panics if every attribute has already been read.
+    #[inline(always)]
+    pub fn read<A: Attribute>(&mut self) -> A {
+        let attribute = &self.fetch.layout.attributes[self.next];
+
+        self.next += 1;
+
+        A::from_components(self.fetch.components(attribute, self.vertex, self.instance))
+    }
+}
+
+impl<V: FetchVertex> VertexState for FetchedVertex<'_, V> {
+    type Vertex = V;
+
+    fn get_vertex_index(&self) -> usize {
+        self.vertex
+    }
+
+    fn get_instance_index(&self) -> usize {
+        self.instance
+    }
+
+    fn get_vertex(&self) -> Self::Vertex {
+        self.fetch.fetch(self.vertex, self.instance)
+    }
+}
+
+/// Converts the bits of a half-precision float.
+#[inline(always)]
+fn half(bits: u16) -> f64 {
+    let sign = if bits >> 15 == 0 { 1.0 } else { -1.0 };
+    let exponent = (bits >> 10 & 0x1f) as i32;
+    let mantissa = (bits & 0x3ff) as f64;
+
+    sign * match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        0x1f if mantissa == 0.0 => f64::INFINITY,
+        0x1f => f64::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f64.powi(exponent - 15),
+    }
+}
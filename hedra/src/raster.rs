@@ -1,12 +1,145 @@
-use std::ops::{AddAssign, BitOr, Mul, Neg, Sub};
+#[cfg(feature = "simd")]
+use std::ops::AddAssign;
+use std::ops::{Add, Mul, Neg, Sub};
 
 use crate::{
-    math::{Vec2, Zero},
+    math::{Bounded, Rect, Vec2, Vec3, Zero},
     pipeline::PixelState,
+    NumberCast,
 };
 
+pub mod oit;
 #[cfg(feature = "simd")]
 pub mod simd;
+pub mod visibility;
+
+macro_rules! edge_test_impl {
+    (f<$t:tt>) => {
+        impl EdgeTest for $t {
+            type Mask = bool;
+
+            #[inline(always)]
+            fn exclude(self) -> Self {
+                self
+            }
+
+            #[inline(always)]
+            fn half(self, _: bool) -> Self {
+                self * 0.5
+            }
+
+            #[inline(always)]
+            fn inside(edges: (Self, Self, Self), inclusive: (bool, bool, bool)) -> Self::Mask {
+                let test = |e: $t, inclusive| if inclusive { e >= 0.0 } else { e > 0.0 };
+
+                test(edges.0, inclusive.0)
+                    && test(edges.1, inclusive.1)
+                    && test(edges.2, inclusive.2)
+            }
+        }
+
+        #[cfg(feature = "simd")]
+        impl<const N: usize> EdgeTest for std::simd::Simd<$t, N>
+        where
+            std::simd::LaneCount<N>: std::simd::SupportedLaneCount,
+        {
+            type Mask = std::simd::Mask<<$t as std::simd::SimdElement>::Mask, N>;
+
+            #[inline(always)]
+            fn exclude(self) -> Self {
+                self
+            }
+
+            #[inline(always)]
+            fn half(self, _: bool) -> Self {
+                self * Self::splat(0.5)
+            }
+
+            #[inline(always)]
+            fn inside(edges: (Self, Self, Self), inclusive: (bool, bool, bool)) -> Self::Mask {
+                use std::simd::cmp::SimdPartialOrd;
+
+                let test = |e: Self, inclusive| {
+                    if inclusive {
+                        e.simd_ge(Self::splat(0.0))
+                    } else {
+                        e.simd_gt(Self::splat(0.0))
+                    }
+                };
+
+                test(edges.0, inclusive.0) & test(edges.1, inclusive.1) & test(edges.2, inclusive.2)
+            }
+        }
+    };
+
+    (f32) => {
+        edge_test_impl!(f<f32>);
+    };
+    (f64) => {
+        edge_test_impl!(f<f64>);
+    };
+
+    (scalar<$t:tt>) => {
+        impl EdgeTest for $t {
+            type Mask = bool;
+
+            #[inline(always)]
+            fn exclude(self) -> Self {
+                self - 1
+            }
+
+            #[inline(always)]
+            fn half(self, round_up: bool) -> Self {
+                (self + round_up as $t) >> 1
+            }
+
+            #[inline(always)]
+            fn inside(edges: (Self, Self, Self), _: (bool, bool, bool)) -> Self::Mask {
+                (edges.0 | edges.1 | edges.2) >= 0
+            }
+        }
+    };
+
+    ($t:tt) => {
+        edge_test_impl!(scalar<$t>);
+
+        #[cfg(feature = "simd")]
+        impl<const N: usize> EdgeTest for std::simd::Simd<$t, N>
+        where
+            std::simd::LaneCount<N>: std::simd::SupportedLaneCount,
+        {
+            type Mask = std::simd::Mask<<$t as std::simd::SimdElement>::Mask, N>;
+
+            #[inline(always)]
+            fn exclude(self) -> Self {
+                self - Self::splat(1)
+            }
+
+            #[inline(always)]
+            fn half(self, round_up: bool) -> Self {
+                (self + Self::splat(round_up as $t)) >> Self::splat(1)
+            }
+
+            #[inline(always)]
+            fn inside(edges: (Self, Self, Self), _: (bool, bool, bool)) -> Self::Mask {
+                use std::simd::cmp::SimdPartialOrd;
+
+                (edges.0 | edges.1 | edges.2).simd_ge(Self::splat(0))
+            }
+        }
+    };
+
+    () => {
+        edge_test_impl!(i8);
+        edge_test_impl!(i16);
+        edge_test_impl!(i32);
+        edge_test_impl!(i64);
+        edge_test_impl!(scalar<i128>);
+        edge_test_impl!(isize);
+        edge_test_impl!(f32);
+        edge_test_impl!(f64);
+    };
+}
 
 pub trait Rasterizer<'a, T>
 where
@@ -14,23 +147,744 @@ where
 {
     type State;
     type Color;
+    /// Mask of the pixels shaded at once, which the pixel closure may return along with their
+    /// color to discard some of them.
+    type Mask;
 
-    fn rasterize<F: Fn(&Self::State) -> Self::Color>(
+    /// Draws the triangles of `list` to the `M` color targets of `tile`, the pixel closure
+    /// returning one color per target.
+    #[inline(always)]
+    fn rasterize<const M: usize, V, R, F>(&mut self, tile: Tile<'_, M>, list: &[V], pixel: F)
+    where
+        V: RasterVertex<T>,
+        R: PixelResult<[Self::Color; M], Self::Mask>,
+        F: Fn(&Self::State) -> R,
+    {
+        self.rasterize_instance(tile, list, 0, pixel);
+    }
+
+    /// Draws like [Rasterizer::rasterize], the triangles belonging to the `instance`th instance
+    /// of an instanced draw.
+    fn rasterize_instance<const M: usize, V, R, F>(
         &mut self,
-        tile: Tile<'a>,
-        list: &'a [Vec2<T>],
+        tile: Tile<'_, M>,
+        list: &[V],
+        instance: usize,
         pixel: F,
-    );
+    ) where
+        V: RasterVertex<T>,
+        R: PixelResult<[Self::Color; M], Self::Mask>,
+        F: Fn(&Self::State) -> R;
+
+    /// Returns the state of the rasterizer, which applies to the following draws.
+    fn state_mut(&mut self) -> &mut RasterizerState;
+
+    /// Returns the statistics collected so far, which pipelines add their vertex counts to.
+    fn statistics_mut(&mut self) -> &mut Statistics;
+}
+
+/// Value returned by pixel closures, either colors or colors along with a mask of the pixels to
+/// keep, the others being discarded before depth testing.
+///
+/// A single color may be returned instead of an array for tiles with a single color target.
+pub trait PixelResult<C, M> {
+    /// Whether pixels may be discarded, in which case depth testing has to wait for the pixel
+    /// closure instead of skipping it for occluded pixels.
+    const DISCARDS: bool;
+
+    fn split(self) -> (C, Option<M>);
+}
+
+/// Vertex the rasterizers accept, positioned in screen space with the y axis pointing down.
+pub trait RasterVertex<T>: Copy {
+    fn position(&self) -> Vec2<T>;
+    /// Returns the depth tested against the tile's depth target, if any.
+    fn depth(&self) -> f32;
+}
+
+impl<T: Copy> RasterVertex<T> for Vec2<T> {
+    #[inline(always)]
+    fn position(&self) -> Vec2<T> {
+        *self
+    }
+
+    #[inline(always)]
+    fn depth(&self) -> f32 {
+        0.0
+    }
+}
+
+impl<T: Copy + NumberCast<f32>> RasterVertex<T> for Vec3<T> {
+    #[inline(always)]
+    fn position(&self) -> Vec2<T> {
+        Vec2 {
+            x: self.x,
+            y: self.y,
+        }
+    }
+
+    #[inline(always)]
+    fn depth(&self) -> f32 {
+        self.z.to_num()
+    }
+}
+
+macro_rules! edge_setup_impl {
+    ($t:tt, $setup:tt, $fallback:tt, $band:expr) => {
+        impl EdgeSetup for $t {
+            type Setup = $setup;
+            type Fallback = $fallback;
+
+            const GUARD_BAND: $setup = $band;
+        }
+    };
+
+    () => {
+        edge_setup_impl!(i8, i32, i16, 1 << 7);
+        edge_setup_impl!(i16, i64, i32, 1 << 15);
+        edge_setup_impl!(i32, i128, i64, 1 << 31);
+        edge_setup_impl!(i64, i64, i64, 1 << 30);
+        edge_setup_impl!(f32, f64, f32, f64::INFINITY);
+        edge_setup_impl!(f64, f64, f64, f64::INFINITY);
+    };
 }
 
+/// Area of a set of `M` color targets to draw to, sharing the same layout.
 #[derive(Debug)]
-pub struct Tile<'a> {
-    pub dst: &'a mut [u32],
+pub struct Tile<'a, const M: usize = 1> {
+    pub targets: [RenderTarget<'a>; M],
+    /// Depth target with the same layout as the color targets, enabling depth testing when
+    /// present.
+    pub depth: Option<&'a mut [f32]>,
+    /// Hierarchical-Z buffer of the depth target, kept up to date as depth is written.
+    pub hiz: Option<&'a mut HiZ>,
+    /// Fragment buffer with the same layout as the color targets. When present, pixels are
+    /// appended to it instead of being written to the targets, to be composited by
+    /// [oit::resolve]. Depth is still tested against the depth target, which usually shouldn't
+    /// be written to while drawing transparent triangles.
+    pub fragments: Option<&'a mut oit::FragmentBuffer>,
     pub dst_width: usize,
     pub position: Vec2<usize>,
     pub dimensions: Vec2<usize>,
 }
 
+/// Color target with `0xAARRGGBB` pixels.
+#[derive(Debug)]
+pub struct RenderTarget<'a> {
+    pub dst: &'a mut [u32],
+    pub write_mask: ColorMask,
+}
+
+/// Channels of the pixels of a color target that get written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorMask {
+    pub r: bool,
+    pub g: bool,
+    pub b: bool,
+    pub a: bool,
+}
+
+impl<'a> RenderTarget<'a> {
+    /// Creates a target writing to every channel of `dst`.
+    pub fn new(dst: &'a mut [u32]) -> Self {
+        Self {
+            dst,
+            write_mask: ColorMask::ALL,
+        }
+    }
+}
+
+impl ColorMask {
+    pub const ALL: Self = Self {
+        r: true,
+        g: true,
+        b: true,
+        a: true,
+    };
+    pub const NONE: Self = Self {
+        r: false,
+        g: false,
+        b: false,
+        a: false,
+    };
+
+    /// Returns the bits of a pixel covered by the enabled channels.
+    #[inline(always)]
+    pub fn bits(self) -> u32 {
+        let channel = |enabled: bool, shift: u32| if enabled { 0xff << shift } else { 0 };
+
+        channel(self.a, 24) | channel(self.r, 16) | channel(self.g, 8) | channel(self.b, 0)
+    }
+}
+
+impl Default for ColorMask {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl<const M: usize> Tile<'_, M> {
+    /// Borrows the targets again for a shorter lifetime, e.g. to draw to the tile several times.
+    pub fn reborrow(&mut self) -> Tile<'_, M> {
+        Tile {
+            targets: self.targets.each_mut().map(|target| RenderTarget {
+                dst: target.dst,
+                write_mask: target.write_mask,
+            }),
+            depth: self.depth.as_deref_mut(),
+            hiz: self.hiz.as_deref_mut(),
+            fragments: self.fragments.as_deref_mut(),
+            dst_width: self.dst_width,
+            position: self.position,
+            dimensions: self.dimensions,
+        }
+    }
+
+    /// Restricts the area drawn to to its intersection with `scissor`, returning `false` if
+    /// nothing is left to draw.
+    pub fn scissor(&mut self, scissor: Rect<usize>) -> bool {
+        let tile = Rect {
+            position: self.position,
+            dimensions: self.dimensions,
+        };
+
+        match tile.intersect(scissor) {
+            Some(area) => {
+                self.position = area.position;
+                self.dimensions = area.dimensions;
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fills the area of every color target with `color`, whatever their write mask.
+    pub fn clear(&mut self, color: u32) {
+        for target in &mut self.targets {
+            for y in self.position.y..self.position.y + self.dimensions.y {
+                let start = y * self.dst_width + self.position.x;
+
+                target.dst[start..start + self.dimensions.x].fill(color);
+            }
+        }
+    }
+
+    /// Fills the area of the depth target with `depth` if any, updating the hierarchical-Z
+    /// buffer.
+    pub fn clear_depth(&mut self, depth: f32) {
+        let Some(dst) = self.depth.as_deref_mut() else {
+            return;
+        };
+
+        for y in self.position.y..self.position.y + self.dimensions.y {
+            let start = y * self.dst_width + self.position.x;
+
+            dst[start..start + self.dimensions.x].fill(depth);
+        }
+
+        if let Some(hiz) = self.hiz.as_deref_mut() {
+            let area = Rect {
+                position: self.position,
+                dimensions: self.dimensions,
+            };
+
+            hiz.clear_area(area, depth);
+        }
+    }
+}
+
+/// Hierarchical-Z buffer, keeping the range of depths within every block of a depth target so
+/// that triangles and blocks can be rejected without testing their pixels.
+///
+/// Blocks are aligned to the origin of the depth target. Rasterizers only cull in tiles whose
+/// position is aligned to blocks the size of their lane layout's, and otherwise reset the blocks
+/// of the tile when writing depth so that they never get culled.
+#[derive(Debug, Clone)]
+pub struct HiZ {
+    block: Vec2<usize>,
+    width: usize,
+    min: Vec<f32>,
+    max: Vec<f32>,
+}
+
+impl HiZ {
+    /// Creates the buffer of a depth target of `dimensions` pixels cleared to `depth`.
+    pub fn new(dimensions: Vec2<usize>, block: Vec2<usize>, depth: f32) -> Self {
+        let width = dimensions.x.div_ceil(block.x);
+        let len = width * dimensions.y.div_ceil(block.y);
+
+        Self {
+            block,
+            width,
+            min: vec![depth; len],
+            max: vec![depth; len],
+        }
+    }
+
+    pub fn block(&self) -> Vec2<usize> {
+        self.block
+    }
+
+    /// Resets every block, along with clearing the depth target to `depth`.
+    pub fn clear(&mut self, depth: f32) {
+        self.min.fill(depth);
+        self.max.fill(depth);
+    }
+
+    /// Recomputes every block from the depth target, after writing to it other than by
+    /// rasterizing with this buffer.
+    pub fn update(&mut self, depth: &[f32], dst_width: usize) {
+        self.min.fill(f32::INFINITY);
+        self.max.fill(f32::NEG_INFINITY);
+
+        for (i, &depth) in depth.iter().enumerate() {
+            let cell = (i / dst_width / self.block.y) * self.width + i % dst_width / self.block.x;
+
+            self.min[cell] = self.min[cell].min(depth);
+            self.max[cell] = self.max[cell].max(depth);
+        }
+    }
+
+    /// Resets the blocks within `area` to `depth`, along with clearing it in the depth target.
+    /// Blocks it only partly covers keep their range, widened to `depth`.
+    fn clear_area(&mut self, area: Rect<usize>, depth: f32) {
+        let end = Vec2 {
+            x: area.position.x + area.dimensions.x,
+            y: area.position.y + area.dimensions.y,
+        };
+
+        for cell in self.cells(area) {
+            let start = Vec2 {
+                x: cell % self.width * self.block.x,
+                y: cell / self.width * self.block.y,
+            };
+            let covered = start.x >= area.position.x
+                && start.y >= area.position.y
+                && start.x + self.block.x <= end.x
+                && start.y + self.block.y <= end.y;
+
+            if covered {
+                self.min[cell] = depth;
+                self.max[cell] = depth;
+            } else {
+                self.min[cell] = self.min[cell].min(depth);
+                self.max[cell] = self.max[cell].max(depth);
+            }
+        }
+    }
+
+    /// Returns the index of the block at `position`, if a block of size `block` starts there.
+    #[cfg(feature = "simd")]
+    #[inline(always)]
+    fn cell(&self, position: Vec2<usize>, block: Vec2<usize>) -> Option<usize> {
+        let aligned = position.x.is_multiple_of(block.x) && position.y.is_multiple_of(block.y);
+
+        (block == self.block && aligned)
+            .then(|| position.y / block.y * self.width + position.x / block.x)
+    }
+
+    /// Returns the range of depths within the blocks overlapping `area`.
+    #[cfg(feature = "simd")]
+    fn range(&self, area: Rect<usize>) -> (f32, f32) {
+        self.cells(area)
+            .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), cell| {
+                (min.min(self.min[cell]), max.max(self.max[cell]))
+            })
+    }
+
+    /// Resets the blocks overlapping `area` to every depth, so that they never get culled.
+    #[cfg(feature = "simd")]
+    fn invalidate(&mut self, area: Rect<usize>) {
+        for cell in self.cells(area) {
+            self.min[cell] = f32::NEG_INFINITY;
+            self.max[cell] = f32::INFINITY;
+        }
+    }
+
+    fn cells(&self, area: Rect<usize>) -> impl Iterator<Item = usize> {
+        let width = self.width;
+        let start = Vec2 {
+            x: area.position.x / self.block.x,
+            y: area.position.y / self.block.y,
+        };
+        let end = Vec2 {
+            x: (area.position.x + area.dimensions.x).div_ceil(self.block.x),
+            y: (area.position.y + area.dimensions.y).div_ceil(self.block.y),
+        };
+
+        (start.y..end.y).flat_map(move |y| (start.x..end.x).map(move |x| y * width + x))
+    }
+}
+
+/// Configuration shared by rasterizers, independent of the pixel closure.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RasterizerState {
+    pub cull_mode: CullMode,
+    pub depth: DepthState,
+    /// Area drawing is restricted to, in the same coordinates as the tile's position.
+    pub scissor: Option<Rect<usize>>,
+    pub conservative: Conservative,
+    pub fill_mode: FillMode,
+    /// How pixels are combined with the color targets, after which their write mask applies.
+    pub blend: BlendMode,
+    /// Computes the fraction of every pixel covered by triangles, passed to the pixel closure as
+    /// an alpha value, rather than covering pixels entirely or not at all.
+    ///
+    /// Coverage is approximated from the distance of pixels to the closest edge of triangles,
+    /// which are traversed as if overestimated whatever the conservative mode.
+    pub antialias: bool,
+}
+
+/// Counters of the work done while drawing, accumulated by rasterizers and pipelines until
+/// reset.
+///
+/// Counts of single draws are obtained with a [Query].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Statistics {
+    /// Pixels passing the depth test and not discarded, or drawn without a depth target.
+    pub samples_passed: u64,
+    /// Vertices run through a vertex shader by a pipeline.
+    pub vertices_shaded: u64,
+    /// Indices of indexed draws whose vertex was already shaded for the same instance, and got
+    /// reused from the post-transform vertex cache.
+    pub vertex_cache_hits: u64,
+    /// Indices of indexed draws whose vertex had to be shaded.
+    pub vertex_cache_misses: u64,
+    /// Triangles discarded before being traversed, by their facing, for being degenerate or for
+    /// falling outside of the tile or behind its depth.
+    pub triangles_culled: u64,
+    pub triangles_rasterized: u64,
+    /// Blocks of pixels whose edges were tested.
+    pub spans_tested: u64,
+    /// Lanes enabled when calling the pixel closure.
+    pub lanes_shaded: u64,
+    /// Lanes disabled when calling the pixel closure, shaded for nothing.
+    pub lanes_masked: u64,
+}
+
+/// Query of the [Statistics] collected between its beginning and end, e.g. to count the
+/// samples passed by a draw for occlusion culling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Query {
+    start: Statistics,
+}
+
+impl Statistics {
+    /// Returns the ratio of lanes doing useful work when calling the pixel closure, or 0 if the
+    /// pixel closure wasn't called.
+    pub fn lane_efficiency(&self) -> f64 {
+        ratio(self.lanes_shaded, self.lanes_shaded + self.lanes_masked)
+    }
+
+    /// Returns the ratio of indices of indexed draws reusing an already shaded vertex, or 0
+    /// without indexed draws.
+    pub fn vertex_cache_hit_rate(&self) -> f64 {
+        ratio(
+            self.vertex_cache_hits,
+            self.vertex_cache_hits + self.vertex_cache_misses,
+        )
+    }
+}
+
+fn ratio(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+impl Query {
+    pub fn begin(statistics: &Statistics) -> Self {
+        Self { start: *statistics }
+    }
+
+    /// Returns the counts collected since the query began.
+    ///
+    /// Statistics reset while the query runs give meaningless counts, which saturate at 0 rather
+    /// than underflowing.
+    pub fn end(self, statistics: &Statistics) -> Statistics {
+        Statistics {
+            samples_passed: statistics
+                .samples_passed
+                .saturating_sub(self.start.samples_passed),
+            vertices_shaded: statistics
+                .vertices_shaded
+                .saturating_sub(self.start.vertices_shaded),
+            vertex_cache_hits: statistics
+                .vertex_cache_hits
+                .saturating_sub(self.start.vertex_cache_hits),
+            vertex_cache_misses: statistics
+                .vertex_cache_misses
+                .saturating_sub(self.start.vertex_cache_misses),
+            triangles_culled: statistics
+                .triangles_culled
+                .saturating_sub(self.start.triangles_culled),
+            triangles_rasterized: statistics
+                .triangles_rasterized
+                .saturating_sub(self.start.triangles_rasterized),
+            spans_tested: statistics
+                .spans_tested
+                .saturating_sub(self.start.spans_tested),
+            lanes_shaded: statistics
+                .lanes_shaded
+                .saturating_sub(self.start.lanes_shaded),
+            lanes_masked: statistics
+                .lanes_masked
+                .saturating_sub(self.start.lanes_masked),
+        }
+    }
+}
+
+/// Depth testing of the pixels drawn to a tile with a depth target.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DepthState {
+    /// Comparison of a pixel's depth against the depth target, which it has to pass to be drawn.
+    pub compare: Compare,
+    pub write: bool,
+    pub bias: DepthBias,
+}
+
+/// Offset added to the depth of triangles, e.g. to keep decals from z-fighting with the surface
+/// they're drawn on, or shadow casters from shadowing themselves.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DepthBias {
+    pub constant: f32,
+    /// Factor of the largest of the triangle's depth slopes along x and y, so that steep
+    /// triangles get offset more.
+    pub slope: f32,
+    /// Largest magnitude of the bias, limiting it towards the sign of the clamp, or zero for no
+    /// limit.
+    pub clamp: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Never,
+    Less,
+    LessEqual,
+    Equal,
+    GreaterEqual,
+    Greater,
+    NotEqual,
+    Always,
+}
+
+/// Which triangles get discarded depending on their winding.
+///
+/// Front-facing triangles are clockwise on screen, with the y axis pointing down.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum CullMode {
+    None,
+    Front,
+    #[default]
+    Back,
+}
+
+/// How the `0xAARRGGBB` colors of pixels are combined with those of the color targets, `a`
+/// being the alpha of the pixel.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Replaces the color of the target.
+    #[default]
+    Replace,
+    /// `src * a + dst * (1 - a)`, the alpha of the target becoming `a + dst * (1 - a)`.
+    Alpha,
+    /// `src + dst * (1 - a)`, for colors premultiplied by their alpha.
+    Premultiplied,
+    /// `src + dst`, saturating.
+    Additive,
+}
+
+/// Which pixels of a triangle get drawn.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum FillMode {
+    #[default]
+    Solid,
+    /// Draws the edges of triangles as lines one pixel wide, ignoring the conservative mode.
+    ///
+    /// Lines end half a pixel past the vertices, however sharp the corners they make. Antialiasing
+    /// only smooths the outer side of the lines.
+    Wireframe,
+    /// Draws the pixel each vertex of a triangle is in, ignoring the conservative and
+    /// antialiasing modes.
+    Point,
+}
+
+/// Conservative rasterization, which covers pixels depending on how much of their area is inside
+/// a triangle rather than whether their center is.
+///
+/// The area of a pixel is the square of one pixel around the coordinates its edges are evaluated
+/// at. Conservative modes ignore the top-left fill convention, as pixels along an edge shared by
+/// two triangles are either covered by both or by none of them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Conservative {
+    #[default]
+    Off,
+    /// Covers every pixel the triangle touches at all.
+    ///
+    /// Edges are moved outwards until they reach the farthest corner of the pixels they touch,
+    /// so pixels close to sharp corners may be covered without touching the triangle.
+    Overestimate,
+    /// Only covers pixels entirely inside the triangle.
+    Underestimate,
+}
+
+impl Default for DepthState {
+    fn default() -> Self {
+        Self {
+            compare: Compare::Less,
+            write: true,
+            bias: DepthBias::default(),
+        }
+    }
+}
+
+#[cfg(feature = "simd")]
+impl Compare {
+    /// Returns whether no depth within `src` passes the comparison against any within `dst`,
+    /// both being ranges of depths.
+    #[inline(always)]
+    fn rejects(self, (src_min, src_max): (f32, f32), (dst_min, dst_max): (f32, f32)) -> bool {
+        match self {
+            Self::Never => true,
+            Self::Less => src_min >= dst_max,
+            Self::LessEqual => src_min > dst_max,
+            Self::Equal => src_max < dst_min || src_min > dst_max,
+            Self::GreaterEqual => src_max < dst_min,
+            Self::Greater => src_max <= dst_min,
+            Self::NotEqual | Self::Always => false,
+        }
+    }
+}
+
+impl CullMode {
+    #[inline(always)]
+    pub fn culls(self, front_facing: bool) -> bool {
+        match self {
+            Self::None => false,
+            Self::Front => front_facing,
+            Self::Back => !front_facing,
+        }
+    }
+}
+
+/// Inside test of a triangle's edge functions, following the top-left fill convention so that
+/// pixels on an edge shared by two triangles are only covered by one of them.
+pub trait EdgeTest: Sized {
+    type Mask;
+
+    /// Adjusts the value of an edge that isn't top-left during setup.
+    ///
+    /// Integers subtract one so that `>= 0` becomes `> 0` and all three edges can still be
+    /// tested at once by ORing their sign bits together, which only works for signed integers.
+    fn exclude(self) -> Self;
+
+    /// Halves the value, rounding integers up or down.
+    fn half(self, round_up: bool) -> Self;
+
+    /// Tests the three edges, where `inclusive` tells which of them are top-left edges.
+    fn inside(edges: (Self, Self, Self), inclusive: (bool, bool, bool)) -> Self::Mask;
+}
+
+/// Coordinate types triangles can be rasterized with.
+///
+/// Triangles are set up in the wider `Setup` type and rebased to the origin of the tile, after
+/// which their edges are evaluated in `Self` whenever they can't overflow it over the tile, and
+/// in `Fallback` otherwise.
+///
+/// Setup multiplies coordinate differences, so vertices have to stay within a guard band for
+/// those products not to overflow `Setup`, e.g. ±2^30 for `i64`. Triangles with vertices outside
+/// of it are culled, while `i32` is set up in `i128` so that any of its triangles can be drawn.
+pub trait EdgeSetup: Copy + Bounded + NumberCast<Self::Setup> {
+    type Setup: Copy
+        + Zero
+        + PartialOrd
+        + Add<Output = Self::Setup>
+        + Sub<Output = Self::Setup>
+        + Mul<Output = Self::Setup>
+        + Neg<Output = Self::Setup>
+        + EdgeTest
+        + NumberCast<Self>
+        + NumberCast<Self::Fallback>;
+    type Fallback: Copy;
+
+    /// Largest magnitude of the coordinates of vertices.
+    const GUARD_BAND: Self::Setup;
+}
+
+/// Depth of a triangle as the plane `z + dx * x + dy * y`, with `x` and `y` relative to the
+/// origin it was set up at.
+#[cfg(feature = "simd")]
+#[derive(Debug, Clone, Copy)]
+struct DepthPlane {
+    z: f32,
+    dx: f32,
+    dy: f32,
+}
+
+#[cfg(feature = "simd")]
+impl DepthPlane {
+    /// Returns the depth bias of the triangle, from the gradients of its plane.
+    #[inline(always)]
+    fn bias(&self, bias: DepthBias) -> f32 {
+        let offset = bias.constant + bias.slope * self.dx.abs().max(self.dy.abs());
+
+        if bias.clamp > 0.0 {
+            offset.min(bias.clamp)
+        } else if bias.clamp < 0.0 {
+            offset.max(bias.clamp)
+        } else {
+            offset
+        }
+    }
+}
+
+/// Interpolated values of a triangle besides its edges.
+#[cfg(feature = "simd")]
+#[derive(Debug, Clone, Copy)]
+struct TrianglePlanes {
+    depth: DepthPlane,
+    /// Segment of each edge, giving the distance in pixels from the triangle, when antialiasing.
+    distance: Option<(Segment, Segment, Segment)>,
+    /// Weight of each vertex, in the order they were passed to the rasterizer.
+    barycentric: (Edge<f32>, Edge<f32>, Edge<f32>),
+}
+
+/// Edge function `a * x + b * y + c`, with `x` and `y` relative to the origin it was set up at.
+#[cfg(feature = "simd")]
+#[derive(Debug, Clone, Copy)]
+struct Edge<T> {
+    a: T,
+    b: T,
+    c: T,
+}
+
+/// Edge of a triangle bounded by its vertices.
+#[cfg(feature = "simd")]
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    /// Signed distance in pixels from the line through the edge, positive inside.
+    distance: Edge<f32>,
+    /// Distance in pixels along the edge from its first vertex.
+    along: Edge<f32>,
+    length: f32,
+}
+
+/// Edge functions of a triangle, along with which of them are top-left.
+///
+/// Back-facing triangles are set up with their winding reversed, so that the inside of every
+/// triangle is where all three edges are positive.
+#[cfg(feature = "simd")]
+#[derive(Debug, Clone, Copy)]
+struct TriangleEdges<T> {
+    edges: (Edge<T>, Edge<T>, Edge<T>),
+    inclusive: (bool, bool, bool),
+    front_facing: bool,
+}
+
+#[cfg(feature = "simd")]
 #[derive(Debug)]
 struct TriangleEdgeState<T> {
     i: usize,
@@ -39,33 +893,114 @@ struct TriangleEdgeState<T> {
     row: (T, T, T),
     edges: (T, T, T),
     last_edges: (T, T, T),
+    inclusive: (bool, bool, bool),
+}
+
+#[cfg(feature = "simd")]
+impl<T> Edge<T> {
+    #[inline(always)]
+    pub fn map<U>(self, f: impl Fn(T) -> U) -> Edge<U> {
+        Edge {
+            a: f(self.a),
+            b: f(self.b),
+            c: f(self.c),
+        }
+    }
+}
+
+#[cfg(feature = "simd")]
+impl<T> TriangleEdges<T> {
+    #[inline(always)]
+    pub fn map<U>(self, f: impl Fn(T) -> U) -> TriangleEdges<U> {
+        let (e1, e2, e3) = self.edges;
+
+        TriangleEdges {
+            edges: (e1.map(&f), e2.map(&f), e3.map(&f)),
+            inclusive: self.inclusive,
+            front_facing: self.front_facing,
+        }
+    }
 }
 
-impl<T: Copy + AddAssign<T> + Sub<Output = T> + Mul<Output = T> + Neg<Output = T>>
-    TriangleEdgeState<T>
+#[cfg(feature = "simd")]
+impl<T: Copy + PartialOrd + Zero + Add<Output = T> + Mul<Output = T> + Neg<Output = T>>
+    TriangleEdges<T>
 {
+    /// Returns whether the edges stay within `max` at every pixel of an area of `extent` pixels.
+    #[inline(always)]
+    pub fn fits(&self, extent: Vec2<T>, max: T) -> bool {
+        let abs = |v: T| if v < T::ZERO { -v } else { v };
+        let fits = |e: Edge<T>| abs(e.c) + abs(e.a) * extent.x + abs(e.b) * extent.y <= max;
+
+        fits(self.edges.0) && fits(self.edges.1) && fits(self.edges.2)
+    }
+
+    /// Classifies the edges over an area of `extent` pixels, returning `None` if the triangle
+    /// is entirely outside of it.
+    ///
+    /// Edges the whole area is inside of are replaced with a constant, so that only edges
+    /// crossing the area are evaluated and the magnitude of triangles extending far outside of
+    /// it doesn't matter.
     #[inline(always)]
-    pub fn new(width: usize, p: Vec2<T>, v1: Vec2<T>, v2: Vec2<T>, v3: Vec2<T>) -> Self {
-        let x1_x3 = v1.x - v3.x;
-        let x2_x1 = v2.x - v1.x;
-        let x3_x2 = v3.x - v2.x;
-        let y1_y3 = v1.y - v3.y;
-        let y2_y1 = v2.y - v1.y;
-        let y3_y2 = v3.y - v2.y;
+    pub fn classify(self, extent: Vec2<T>) -> Option<Self> {
+        let min = |v: T| if v < T::ZERO { v } else { T::ZERO };
+        let max = |v: T| if v > T::ZERO { v } else { T::ZERO };
+        let classify = |e: Edge<T>, inclusive: bool| {
+            let x = e.a * extent.x;
+            let y = e.b * extent.y;
+
+            if e.c + max(x) + max(y) < T::ZERO {
+                None
+            } else if e.c + min(x) + min(y) > T::ZERO {
+                let inside = Edge {
+                    a: T::ZERO,
+                    b: T::ZERO,
+                    c: T::ZERO,
+                };
+
+                Some((inside, true))
+            } else {
+                Some((e, inclusive))
+            }
+        };
+
+        let (e1, i1) = classify(self.edges.0, self.inclusive.0)?;
+        let (e2, i2) = classify(self.edges.1, self.inclusive.1)?;
+        let (e3, i3) = classify(self.edges.2, self.inclusive.2)?;
+
+        Some(Self {
+            edges: (e1, e2, e3),
+            inclusive: (i1, i2, i3),
+            front_facing: self.front_facing,
+        })
+    }
+}
 
+#[cfg(feature = "simd")]
+impl<T: Copy + AddAssign<T> + Add<Output = T> + Mul<Output = T> + EdgeTest> TriangleEdgeState<T> {
+    /// Starts evaluating the edges at the offsets `lane`, stepping `stride` pixels at a time
+    /// across rows of `width` steps.
+    #[inline(always)]
+    pub fn new(width: usize, stride: Vec2<T>, lane: Vec2<T>, triangle: TriangleEdges<T>) -> Self {
+        let TriangleEdges {
+            edges: (e1, e2, e3),
+            inclusive,
+            ..
+        } = triangle;
         let edges = (
-            (x3_x2) * (p.y - v2.y) - (y3_y2) * (p.x - v2.x),
-            (x1_x3) * (p.y - v3.y) - (y1_y3) * (p.x - v3.x),
-            (x2_x1) * (p.y - v1.y) - (y2_y1) * (p.x - v1.x),
+            e1.c + e1.a * lane.x + e1.b * lane.y,
+            e2.c + e2.a * lane.x + e2.b * lane.y,
+            e3.c + e3.a * lane.x + e3.b * lane.y,
         );
 
         Self {
             i: 0,
             width,
-            step: (-y3_y2, -y1_y3, -y2_y1),
-            row: (x3_x2, x1_x3, x2_x1),
+            step: (e1.a * stride.x, e2.a * stride.x, e3.a * stride.x),
+            row: (e1.b * stride.y, e2.b * stride.y, e3.b * stride.y),
             edges,
             last_edges: edges,
+            inclusive,
         }
     }
 
@@ -96,21 +1031,229 @@ impl<T: Copy + AddAssign<T> + Sub<Output = T> + Mul<Output = T> + Neg<Output = T
         self.next_row_test();
     }
 
-    #[allow(dead_code)] // todo: remove this line when scalar triangle rasterizer is implemented
     #[inline(always)]
-    pub fn test(&self) -> bool
-    where
-        T: Zero + BitOr<Output = T> + PartialOrd,
-    {
-        (self.edges.0 | self.edges.1 | self.edges.2) >= T::ZERO
+    pub fn mask(&self) -> T::Mask {
+        T::inside(self.edges, self.inclusive)
     }
+}
 
-    #[cfg(feature = "simd")]
-    #[inline(always)]
-    pub fn mask(&self) -> T::Mask
-    where
-        T: Zero + BitOr<Output = T> + std::simd::prelude::SimdPartialOrd,
+/// Sets up the edge functions of a triangle in `T::Setup`, relative to `origin`, applying the
+/// fill convention to the edges that aren't top-left (see [top_left]).
+///
+/// With conservative rasterization, the edges are instead offset by how much they change between
+/// the center of a pixel and its farthest corner, outwards to overestimate and inwards to
+/// underestimate.
+///
+/// Returns `None` for degenerate triangles, which have no area, and for triangles with vertices
+/// outside of the guard band.
+#[cfg(feature = "simd")]
+#[inline(always)]
+fn setup<T: EdgeSetup>(
+    origin: Vec2<usize>,
+    (v1, v2, v3): (Vec2<T>, Vec2<T>, Vec2<T>),
+    conservative: Conservative,
+) -> Option<TriangleEdges<T::Setup>>
+where
+    usize: NumberCast<T::Setup>,
+{
+    let origin = Vec2 {
+        x: origin.x.to_num(),
+        y: origin.y.to_num(),
+    };
+    let widen = |v: Vec2<T>| Vec2 {
+        x: v.x.to_num(),
+        y: v.y.to_num(),
+    };
+    let (v1, v2, v3) = (widen(v1), widen(v2), widen(v3));
+
+    // written so that NaN coordinates pass, only overflow being checked for
+    if [v1.x, v1.y, v2.x, v2.y, v3.x, v3.y]
+        .into_iter()
+        .any(|v| v < -T::GUARD_BAND || v > T::GUARD_BAND)
     {
-        (self.edges.0 | self.edges.1 | self.edges.2).simd_ge(T::ZERO)
+        return None;
     }
+
+    let area = (v3.x - v2.x) * (v1.y - v2.y) - (v3.y - v2.y) * (v1.x - v2.x);
+
+    if area == T::Setup::ZERO {
+        return None;
+    }
+
+    let front_facing = area > T::Setup::ZERO;
+    let (v2, v3) = if front_facing { (v2, v3) } else { (v3, v2) };
+
+    let abs = |v: T::Setup| if v < T::Setup::ZERO { -v } else { v };
+
+    let edge = |a: Vec2<T::Setup>, b: Vec2<T::Setup>| {
+        let edge_a = a.y - b.y;
+        let edge_b = b.x - a.x;
+        let c = edge_a * (origin.x - a.x) + edge_b * (origin.y - a.y);
+
+        // integer edges only take whole values, so rounding the offset keeps exactly the pixels
+        // the unrounded offset edge is non-negative at
+        let offset = abs(edge_a) + abs(edge_b);
+        let (c, inclusive) = match conservative {
+            Conservative::Off if top_left(a, b) => (c, true),
+            Conservative::Off => (c.exclude(), false),
+            Conservative::Overestimate => (c + offset.half(false), true),
+            Conservative::Underestimate => (c - offset.half(true), true),
+        };
+
+        let edge = Edge {
+            a: edge_a,
+            b: edge_b,
+            c,
+        };
+
+        (edge, inclusive)
+    };
+
+    let (e1, i1) = edge(v2, v3);
+    let (e2, i2) = edge(v3, v1);
+    let (e3, i3) = edge(v1, v2);
+
+    Some(TriangleEdges {
+        edges: (e1, e2, e3),
+        inclusive: (i1, i2, i3),
+        front_facing,
+    })
 }
+
+/// Sets up the depth plane of a non-degenerate triangle relative to `origin`.
+#[cfg(feature = "simd")]
+#[inline(always)]
+fn depth_plane<T: NumberCast<f64>, V: RasterVertex<T>>(
+    origin: Vec2<usize>,
+    (v1, v2, v3): (V, V, V),
+) -> DepthPlane {
+    let point = |v: V| {
+        let p = v.position();
+
+        Vec3 {
+            x: p.x.to_num(),
+            y: p.y.to_num(),
+            z: v.depth() as f64,
+        }
+    };
+    let (p1, p2, p3) = (point(v1), point(v2), point(v3));
+
+    let u = Vec3 {
+        x: p2.x - p1.x,
+        y: p2.y - p1.y,
+        z: p2.z - p1.z,
+    };
+    let v = Vec3 {
+        x: p3.x - p1.x,
+        y: p3.y - p1.y,
+        z: p3.z - p1.z,
+    };
+
+    // normal of the plane, whose z is twice the triangle's area
+    let nx = u.y * v.z - u.z * v.y;
+    let ny = u.z * v.x - u.x * v.z;
+    let nz = u.x * v.y - u.y * v.x;
+
+    let dx = -nx / nz;
+    let dy = -ny / nz;
+
+    DepthPlane {
+        z: (p1.z + dx * (origin.x as f64 - p1.x) + dy * (origin.y as f64 - p1.y)) as f32,
+        dx: dx as f32,
+        dy: dy as f32,
+    }
+}
+
+/// Sets up the edge segments of a non-degenerate triangle relative to `origin`, normalized so
+/// that they give distances in pixels, positive inside.
+#[cfg(feature = "simd")]
+#[inline(always)]
+fn distance_edges<T: NumberCast<f64>>(
+    origin: Vec2<usize>,
+    (v1, v2, v3): (Vec2<T>, Vec2<T>, Vec2<T>),
+) -> (Segment, Segment, Segment) {
+    let point = |v: Vec2<T>| Vec2::<f64> {
+        x: v.x.to_num(),
+        y: v.y.to_num(),
+    };
+    let (v1, v2, v3) = (point(v1), point(v2), point(v3));
+
+    let area = (v3.x - v2.x) * (v1.y - v2.y) - (v3.y - v2.y) * (v1.x - v2.x);
+    let (v2, v3) = if area > 0.0 { (v2, v3) } else { (v3, v2) };
+
+    let edge = |a: Vec2<f64>, b: Vec2<f64>| {
+        let edge_a = a.y - b.y;
+        let edge_b = b.x - a.x;
+        let length = (edge_a * edge_a + edge_b * edge_b).sqrt();
+        let offset = Vec2 {
+            x: origin.x as f64 - a.x,
+            y: origin.y as f64 - a.y,
+        };
+
+        Segment {
+            distance: Edge {
+                a: edge_a / length,
+                b: edge_b / length,
+                c: (edge_a * offset.x + edge_b * offset.y) / length,
+            }
+            .map(|v| v as f32),
+            along: Edge {
+                a: edge_b / length,
+                b: -edge_a / length,
+                c: (edge_b * offset.x - edge_a * offset.y) / length,
+            }
+            .map(|v| v as f32),
+            length: length as f32,
+        }
+    };
+
+    (edge(v2, v3), edge(v3, v1), edge(v1, v2))
+}
+
+/// Sets up the edges of a non-degenerate triangle relative to `origin`, normalized by its area
+/// so that they give the barycentric coordinates of each vertex, whatever its winding.
+#[cfg(feature = "simd")]
+#[inline(always)]
+fn barycentric_edges<T: NumberCast<f64>>(
+    origin: Vec2<usize>,
+    (v1, v2, v3): (Vec2<T>, Vec2<T>, Vec2<T>),
+) -> (Edge<f32>, Edge<f32>, Edge<f32>) {
+    let point = |v: Vec2<T>| Vec2::<f64> {
+        x: v.x.to_num(),
+        y: v.y.to_num(),
+    };
+    let (v1, v2, v3) = (point(v1), point(v2), point(v3));
+
+    // each edge is the area of the triangle it makes with the pixel, signed the same way
+    let area = (v3.x - v2.x) * (v1.y - v2.y) - (v3.y - v2.y) * (v1.x - v2.x);
+
+    let edge = |a: Vec2<f64>, b: Vec2<f64>| {
+        let edge_a = a.y - b.y;
+        let edge_b = b.x - a.x;
+
+        Edge {
+            a: edge_a / area,
+            b: edge_b / area,
+            c: (edge_a * (origin.x as f64 - a.x) + edge_b * (origin.y as f64 - a.y)) / area,
+        }
+        .map(|v| v as f32)
+    };
+
+    (edge(v2, v3), edge(v3, v1), edge(v1, v2))
+}
+
+/// Returns whether the edge from `a` to `b` of a clockwise triangle is a top or left edge,
+/// whose pixels are considered inside the triangle.
+///
+/// The y axis points down, so a left edge goes up and a top edge goes right.
+#[cfg(feature = "simd")]
+#[inline(always)]
+fn top_left<T: Copy + Zero + PartialOrd + Sub<Output = T>>(a: Vec2<T>, b: Vec2<T>) -> bool {
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+
+    dy < T::ZERO || (dy == T::ZERO && dx > T::ZERO)
+}
+
+edge_test_impl!();
+edge_setup_impl!();
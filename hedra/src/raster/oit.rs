@@ -0,0 +1,119 @@
+//! Order-independent transparency with an A-buffer of fixed-depth per-pixel fragment arrays.
+//!
+//! Transparent triangles are drawn to tiles with a [FragmentBuffer], which keeps the color and
+//! depth of every pixel drawn instead of blending it. [resolve] then sorts the fragments of every
+//! pixel and composites them onto the color target from back to front, whatever order the
+//! triangles were drawn in.
+
+use super::Tile;
+
+/// Color and depth of a pixel drawn to a [FragmentBuffer].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Fragment {
+    /// `0xAARRGGBB` color with straight alpha.
+    pub color: u32,
+    pub depth: f32,
+}
+
+/// Fragments drawn to every pixel of a target, keeping up to a fixed number of them per pixel.
+///
+/// Once a pixel is full, the farthest of its fragments, smaller depths being nearer, gets
+/// replaced by nearer ones and the others are dropped.
+#[derive(Debug, Clone)]
+pub struct FragmentBuffer {
+    layers: usize,
+    counts: Vec<u32>,
+    fragments: Vec<Fragment>,
+}
+
+impl FragmentBuffer {
+    /// Creates an empty buffer of `len` pixels, keeping up to `layers` fragments per pixel.
+    pub fn new(len: usize, layers: usize) -> Self {
+        Self {
+            layers,
+            counts: vec![0; len],
+            fragments: vec![Fragment::default(); len * layers],
+        }
+    }
+
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.fill(0);
+    }
+
+    /// Returns the fragments of the pixel at `index`, in the order they were inserted.
+    pub fn fragments(&self, index: usize) -> &[Fragment] {
+        let start = index * self.layers;
+
+        &self.fragments[start..start + self.counts[index] as usize]
+    }
+
+    #[inline(always)]
+    pub fn insert(&mut self, index: usize, fragment: Fragment) {
+        let start = index * self.layers;
+        let count = self.counts[index] as usize;
+
+        if count < self.layers {
+            self.fragments[start + count] = fragment;
+            self.counts[index] += 1;
+        } else if let Some(farthest) = self.fragments[start..start + count]
+            .iter_mut()
+            .max_by(|a, b| a.depth.total_cmp(&b.depth))
+        {
+            if fragment.depth < farthest.depth {
+                *farthest = fragment;
+            }
+        }
+    }
+}
+
+/// Composites the fragments of the pixels of `tile` onto its color target from back to front,
+/// emptying them.
+pub fn resolve(fragments: &mut FragmentBuffer, tile: Tile<'_>) {
+    let [target] = tile.targets;
+    let bits = target.write_mask.bits();
+    let layers = fragments.layers;
+
+    for y in tile.position.y..tile.position.y + tile.dimensions.y {
+        let row = y * tile.dst_width;
+
+        for x in tile.position.x..tile.position.x + tile.dimensions.x {
+            let index = row + x;
+            let count = fragments.counts[index] as usize;
+
+            if count == 0 {
+                continue;
+            }
+
+            let pixel = &mut fragments.fragments[index * layers..index * layers + count];
+
+            pixel.sort_unstable_by(|a, b| b.depth.total_cmp(&a.depth));
+
+            let color = pixel
+                .iter()
+                .fold(target.dst[index], |dst, fragment| over(fragment.color, dst));
+            let dst = &mut target.dst[index];
+
+            *dst = *dst & !bits | color & bits;
+            fragments.counts[index] = 0;
+        }
+    }
+}
+
+/// Blends `src` over `dst` by the alpha of `src`.
+#[inline(always)]
+fn over(src: u32, dst: u32) -> u32 {
+    let alpha = src >> 24;
+    let channel = |shift: u32| {
+        let src = (src >> shift) & 0xff;
+        let dst = (dst >> shift) & 0xff;
+
+        ((src * alpha + dst * (255 - alpha) + 127) / 255) << shift
+    };
+    let dst_alpha = dst >> 24;
+
+    (alpha + (dst_alpha * (255 - alpha) + 127) / 255) << 24 | channel(16) | channel(8) | channel(0)
+}
@@ -1,13 +1,30 @@
 use std::{
-    ops::{Add, BitOr, Div, Mul, Neg, Rem, Sub},
-    simd::{cmp::SimdPartialOrd, LaneCount, Mask, Simd, SimdElement, SupportedLaneCount},
+    marker::PhantomData,
+    ops::{Add, AddAssign, Mul},
+    simd::{
+        cmp::{SimdOrd, SimdPartialEq, SimdPartialOrd},
+        num::SimdFloat,
+        LaneCount, Mask, Select, Simd, SimdElement, StdFloat, SupportedLaneCount,
+    },
 };
 
 use crate::{
-    math::Zero, pipeline::PixelState, raster::TriangleEdgeState, NumberCast, SimdTransmute,
+    pipeline::PixelState,
+    raster::{
+        barycentric_edges, depth_plane, distance_edges, setup, BlendMode, ColorMask, Compare,
+        Conservative, DepthPlane, Edge, EdgeSetup, EdgeTest, FillMode, PixelResult, RasterVertex,
+        RasterizerState, RenderTarget, Segment, Statistics, TriangleEdgeState, TriangleEdges,
+        TrianglePlanes,
+    },
+    NumberCast,
 };
 
-use super::{Rasterizer, Tile, Vec2};
+use super::{oit::Fragment, visibility::Visibility, Rasterizer, Rect, Tile, Vec2, Vec3};
+
+pub mod dispatch;
+pub mod layout;
+
+use layout::{LaneLayout, RowLayout};
 
 #[derive(Debug, Clone)]
 pub struct SimdTrianglePixelState<T, const N: usize>
@@ -16,15 +33,41 @@ where
     T: SimdElement,
 {
     pub pixel: Vec2<Simd<T, N>>,
+    /// Coordinates of every pixel as floats.
+    pub position: Vec2<Simd<f32, N>>,
+    /// Depth of the triangle at every pixel, interpolated from the depth of its vertices.
+    pub depth: Simd<f32, N>,
+    /// Fraction of every pixel covered by the triangle when antialiasing, one otherwise.
+    pub coverage: Simd<f32, N>,
+    /// Weight of each vertex of the triangle at every pixel, those of a point being one for the
+    /// vertex drawn and zero for the others.
+    pub barycentric: Vec3<Simd<f32, N>>,
+    /// Index of the triangle in the list passed to [Rasterizer::rasterize].
+    pub primitive: usize,
+    /// Index of the instance the triangle belongs to.
+    pub instance: usize,
+    pub front_facing: bool,
 }
 
+/// Triangle rasterizer evaluating `N` pixels at once, arranged according to the lane layout `L`.
+///
+/// `T` may be a signed integer or a floating-point type, the latter allowing sub-pixel vertices.
+/// Pixels are sampled at their integer coordinates.
+///
+/// Narrow types such as `i16` fit twice as many lanes in a register. Triangles are set up in
+/// wider precision and rebased to the tile, and only those whose edges would overflow `T` over
+/// the tile fall back to [EdgeSetup::Fallback], e.g. `i32` for `i16`.
 #[derive(Debug)]
-pub struct SimdTriangleRasterizer<T, const N: usize>
+pub struct SimdTriangleRasterizer<T, const N: usize, L = RowLayout>
 where
     LaneCount<N>: SupportedLaneCount,
     T: SimdElement,
 {
-    n_vec: Simd<T, N>,
+    pub state: RasterizerState,
+    pub statistics: Statistics,
+    lane: Vec2<Simd<T, N>>,
+    depth_lane: Vec2<Simd<f32, N>>,
+    layout: PhantomData<L>,
 }
 
 impl<T, const N: usize> PixelState for SimdTrianglePixelState<T, N>
@@ -33,108 +76,820 @@ where
     T: SimdElement,
 {
     type Pixel = Vec2<Simd<T, N>>;
+    type FragCoord = Vec3<Simd<f32, N>>;
+    type Barycentric = Vec3<Simd<f32, N>>;
 
     fn get_pixel(&self) -> Self::Pixel {
         self.pixel
     }
+
+    fn get_frag_coord(&self) -> Self::FragCoord {
+        Vec3 {
+            x: self.position.x,
+            y: self.position.y,
+            z: self.depth,
+        }
+    }
+
+    fn get_barycentric(&self) -> Self::Barycentric {
+        self.barycentric
+    }
+
+    fn get_primitive_index(&self) -> usize {
+        self.primitive
+    }
+
+    fn get_instance_index(&self) -> usize {
+        self.instance
+    }
+
+    fn get_front_facing(&self) -> bool {
+        self.front_facing
+    }
+
+    /// Pixels are sampled once, so this is always `0`.
+    fn get_sample_index(&self) -> usize {
+        0
+    }
+}
+
+impl<const N: usize> PixelResult<[Simd<u32, N>; 1], Mask<i32, N>> for Simd<u32, N>
+where
+    LaneCount<N>: SupportedLaneCount,
+{
+    const DISCARDS: bool = false;
+
+    #[inline(always)]
+    fn split(self) -> ([Simd<u32, N>; 1], Option<Mask<i32, N>>) {
+        ([self], None)
+    }
+}
+
+impl<const N: usize> PixelResult<[Simd<u32, N>; 1], Mask<i32, N>> for (Simd<u32, N>, Mask<i32, N>)
+where
+    LaneCount<N>: SupportedLaneCount,
+{
+    const DISCARDS: bool = true;
+
+    #[inline(always)]
+    fn split(self) -> ([Simd<u32, N>; 1], Option<Mask<i32, N>>) {
+        ([self.0], Some(self.1))
+    }
+}
+
+impl<const N: usize, const M: usize> PixelResult<[Simd<u32, N>; M], Mask<i32, N>>
+    for [Simd<u32, N>; M]
+where
+    LaneCount<N>: SupportedLaneCount,
+{
+    const DISCARDS: bool = false;
+
+    #[inline(always)]
+    fn split(self) -> ([Simd<u32, N>; M], Option<Mask<i32, N>>) {
+        (self, None)
+    }
 }
 
-impl<T, const N: usize> Default for SimdTriangleRasterizer<T, N>
+impl<const N: usize, const M: usize> PixelResult<[Simd<u32, N>; M], Mask<i32, N>>
+    for ([Simd<u32, N>; M], Mask<i32, N>)
+where
+    LaneCount<N>: SupportedLaneCount,
+{
+    const DISCARDS: bool = true;
+
+    #[inline(always)]
+    fn split(self) -> ([Simd<u32, N>; M], Option<Mask<i32, N>>) {
+        (self.0, Some(self.1))
+    }
+}
+
+impl<T, const N: usize, L> Default for SimdTriangleRasterizer<T, N, L>
 where
     LaneCount<N>: SupportedLaneCount,
     T: SimdElement,
+    L: LaneLayout<N>,
     usize: NumberCast<T>,
 {
     fn default() -> Self {
         Self {
-            n_vec: Simd::<T, N>::from_slice(&(0..N).map(|i| i.to_num()).collect::<Vec<T>>()),
+            state: Default::default(),
+            statistics: Default::default(),
+            lane: L::lanes(),
+            depth_lane: L::lanes::<f32>(),
+            layout: PhantomData,
         }
     }
 }
 
-impl<T, const N: usize> Rasterizer<'_, T> for SimdTriangleRasterizer<T, N>
+impl<T, const N: usize, L> Rasterizer<'_, T> for SimdTriangleRasterizer<T, N, L>
 where
     LaneCount<N>: SupportedLaneCount,
-    T: Default + SimdElement + NumberCast<usize>,
-    Simd<T, N>: Zero
+    T: SimdElement + EdgeSetup + NumberCast<f64>,
+    T::Fallback: SimdElement,
+    L: LaneLayout<N>,
+    Simd<T, N>: AddAssign
         + Add<Output = Simd<T, N>>
-        + Sub<Output = Simd<T, N>>
         + Mul<Output = Simd<T, N>>
-        + Div<Output = Simd<T, N>>
-        + Rem<Output = Simd<T, N>>
-        + Neg<Output = Simd<T, N>>
-        + BitOr<Output = Simd<T, N>>
         + SimdPartialOrd<Mask = Mask<T::Mask, N>>
-        + SimdTransmute<T, N>,
-    Mask<i32, N>: From<Mask<T::Mask, N>>,
-    usize: NumberCast<T>,
+        + EdgeTest<Mask = Mask<T::Mask, N>>,
+    Simd<T::Fallback, N>: AddAssign
+        + Add<Output = Simd<T::Fallback, N>>
+        + Mul<Output = Simd<T::Fallback, N>>
+        + EdgeTest<Mask = Mask<<T::Fallback as SimdElement>::Mask, N>>,
+    Mask<i32, N>: From<Mask<T::Mask, N>> + From<Mask<<T::Fallback as SimdElement>::Mask, N>>,
+    usize: NumberCast<T> + NumberCast<T::Setup> + NumberCast<T::Fallback>,
 {
     type State = SimdTrianglePixelState<T, N>;
     type Color = Simd<u32, N>;
+    type Mask = Mask<i32, N>;
 
-    fn rasterize<F: Fn(&Self::State) -> Self::Color>(
+    // inlined so that the dispatcher's kernels get compiled with their target features
+    #[inline(always)]
+    fn rasterize_instance<const M: usize, V, R, F>(
         &mut self,
-        tile: Tile<'_>,
-        list: &'_ [Vec2<T>],
+        mut tile: Tile<'_, M>,
+        list: &[V],
+        instance: usize,
         pixel: F,
-    ) {
-        debug_assert!(list.len() % 3 == 0);
-
-        let start = tile.position.y * tile.dimensions.x + tile.position.x;
-        let end = (tile.position.y + tile.dimensions.y) * tile.dimensions.x
-            + (tile.position.x + tile.dimensions.x);
-        let edge_width = tile.dimensions.x / N;
+    ) where
+        V: RasterVertex<T>,
+        R: PixelResult<[Self::Color; M], Self::Mask>,
+        F: Fn(&Self::State) -> R,
+    {
+        debug_assert!(list.len().is_multiple_of(3));
 
-        let start_vec = self.n_vec + [start.to_num(); N].into();
-        let width_vec = Simd::<T, N>::from_slice(&[tile.dimensions.x.to_num(); N]);
+        // the scissor rectangle only shrinks the area traversed, so that no block outside of it
+        // is ever tested
+        if let Some(scissor) = self.state.scissor {
+            if !tile.scissor(scissor) {
+                return;
+            }
+        }
 
-        let state = SimdTrianglePixelState {
-            pixel: Vec2 {
-                x: start_vec % width_vec,
-                y: start_vec / width_vec,
-            },
+        let extent = Vec2 {
+            x: tile.dimensions.x.next_multiple_of(L::WIDTH).to_num(),
+            y: tile.dimensions.y.next_multiple_of(L::HEIGHT).to_num(),
         };
+        let max = T::MAX.to_num();
 
-        let mut iter = list.iter().map(|v| Vec2 {
-            x: Simd::<T, N>::from_slice(&[v.x; N]),
-            y: Simd::<T, N>::from_slice(&[v.y; N]),
-        });
+        // range of depths of the whole tile, which only changes in the direction of the depth
+        // test while drawing and thus stays conservative
+        let range = match (&tile.depth, tile.hiz.as_deref_mut()) {
+            (Some(_), Some(hiz)) => {
+                let area = Rect {
+                    position: tile.position,
+                    dimensions: tile.dimensions,
+                };
+                let block = Vec2 {
+                    x: L::WIDTH,
+                    y: L::HEIGHT,
+                };
+
+                if hiz.cell(tile.position, block).is_some() {
+                    Some(hiz.range(area))
+                } else {
+                    // blocks of the tile straddle those of the buffer, which can't be kept up
+                    // to date
+                    if self.state.depth.write {
+                        hiz.invalidate(area);
+                    }
 
-        while let (Some(v1), Some(v2), Some(v3)) = (iter.next(), iter.next(), iter.next()) {
-            let mut state = state.clone();
-
-            let mut edge = TriangleEdgeState::new(
-                edge_width,
-                Vec2 {
-                    x: state.pixel.x,
-                    y: state.pixel.y,
-                },
-                v1,
-                v2,
-                v3,
+                    None
+                }
+            }
+            _ => None,
+        };
+
+        for (primitive, triangle) in list.chunks_exact(3).enumerate() {
+            let vertices = (triangle[0], triangle[1], triangle[2]);
+            let positions = (
+                vertices.0.position(),
+                vertices.1.position(),
+                vertices.2.position(),
             );
 
-            for i in (start..end).step_by(N) {
-                let mask = edge.mask();
+            let distance = self.state.antialias || self.state.fill_mode == FillMode::Wireframe;
+            let conservative = if distance {
+                Conservative::Overestimate
+            } else {
+                self.state.conservative
+            };
+
+            let Some(triangle) = setup(tile.position, positions, conservative) else {
+                self.statistics.triangles_culled += 1;
+                continue;
+            };
+
+            if self.state.cull_mode.culls(triangle.front_facing) {
+                self.statistics.triangles_culled += 1;
+                continue;
+            }
+
+            let mut depth = depth_plane(tile.position, vertices);
+            let bias = depth.bias(self.state.depth.bias);
+
+            if let Some(range) = range {
+                // overestimated triangles cover pixel centers up to half a pixel away from them
+                // on each axis, where the depth plane goes beyond the depths of the vertices
+                let margin = if conservative == Conservative::Overestimate {
+                    0.5 * (depth.dx.abs() + depth.dy.abs())
+                } else {
+                    0.0
+                };
+                let depths = [vertices.0, vertices.1, vertices.2].map(|v| v.depth() + bias);
+                let min = depths[0].min(depths[1]).min(depths[2]) - margin;
+                let max = depths[0].max(depths[1]).max(depths[2]) + margin;
+
+                if self.state.depth.compare.rejects((min, max), range) {
+                    self.statistics.triangles_culled += 1;
+                    continue;
+                }
+            }
+
+            if self.state.fill_mode == FillMode::Point {
+                self.statistics.triangles_rasterized += 1;
+
+                for (index, vertex) in [vertices.0, vertices.1, vertices.2].into_iter().enumerate()
+                {
+                    let depth = vertex.depth() + bias;
+
+                    self.rasterize_point(
+                        &mut tile,
+                        primitive,
+                        instance,
+                        index,
+                        vertex,
+                        depth,
+                        triangle.front_facing,
+                        &pixel,
+                    );
+                }
+
+                continue;
+            }
+
+            let Some(triangle) = triangle.classify(extent) else {
+                self.statistics.triangles_culled += 1;
+                continue;
+            };
+
+            self.statistics.triangles_rasterized += 1;
+
+            depth.z += bias;
+
+            let planes = TrianglePlanes {
+                depth,
+                distance: distance.then(|| distance_edges(tile.position, positions)),
+                barycentric: barycentric_edges(tile.position, positions),
+            };
+
+            if triangle.fits(extent, max) {
+                let triangle = triangle.map(|v| -> T { v.to_num() });
+
+                self.rasterize_triangle(
+                    &mut tile, self.lane, primitive, instance, triangle, planes, &pixel,
+                );
+            } else {
+                let triangle = triangle.map(|v| -> T::Fallback { v.to_num() });
+
+                self.rasterize_triangle(
+                    &mut tile,
+                    L::lanes(),
+                    primitive,
+                    instance,
+                    triangle,
+                    planes,
+                    &pixel,
+                );
+            }
+        }
+    }
+
+    fn state_mut(&mut self) -> &mut RasterizerState {
+        &mut self.state
+    }
+
+    fn statistics_mut(&mut self) -> &mut Statistics {
+        &mut self.statistics
+    }
+}
+
+impl<T, const N: usize, L> SimdTriangleRasterizer<T, N, L>
+where
+    LaneCount<N>: SupportedLaneCount,
+    T: SimdElement,
+    L: LaneLayout<N>,
+    Simd<T, N>: Add<Output = Simd<T, N>> + SimdPartialOrd<Mask = Mask<T::Mask, N>>,
+    Mask<i32, N>: From<Mask<T::Mask, N>>,
+    usize: NumberCast<T>,
+{
+    /// Traverses the tile with the edges evaluated in `E`, `lane` being the offsets of the
+    /// lanes in that type, shading the `primitive`th triangle of the list of the `instance`th
+    /// instance.
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn rasterize_triangle<const M: usize, E, R, F>(
+        &mut self,
+        tile: &mut Tile<'_, M>,
+        lane: Vec2<Simd<E, N>>,
+        primitive: usize,
+        instance: usize,
+        triangle: TriangleEdges<E>,
+        planes: TrianglePlanes,
+        pixel: &F,
+    ) where
+        E: SimdElement,
+        Simd<E, N>: AddAssign
+            + Add<Output = Simd<E, N>>
+            + Mul<Output = Simd<E, N>>
+            + EdgeTest<Mask = Mask<E::Mask, N>>,
+        Mask<i32, N>: From<Mask<E::Mask, N>>,
+        usize: NumberCast<E>,
+        R: PixelResult<[Simd<u32, N>; M], Mask<i32, N>>,
+        F: Fn(&SimdTrianglePixelState<T, N>) -> R,
+    {
+        let width = tile.dimensions.x.div_ceil(L::WIDTH);
+        let stride = Vec2 {
+            x: Simd::splat(L::WIDTH.to_num()),
+            y: Simd::splat(L::HEIGHT.to_num()),
+        };
+        let bounds = Vec2 {
+            x: Simd::splat(tile.dimensions.x.to_num()),
+            y: Simd::splat(tile.dimensions.y.to_num()),
+        };
+        let origin = Vec2 {
+            x: self.lane.x + Simd::splat(tile.position.x.to_num()),
+            y: self.lane.y + Simd::splat(tile.position.y.to_num()),
+        };
+
+        let mut edge = TriangleEdgeState::new(width, stride, lane, triangle.map(Simd::splat));
+
+        for y in (0..tile.dimensions.y).step_by(L::HEIGHT) {
+            for x in (0..tile.dimensions.x).step_by(L::WIDTH) {
+                let mut mask = Mask::<i32, N>::from(edge.mask());
 
                 edge.step();
+                self.statistics.spans_tested += 1;
 
-                if mask.any() {
-                    let i_vec = self.n_vec + [i.to_num(); N].into();
+                // lanes of blocks straddling the tile's right or bottom edge
+                if x + L::WIDTH > tile.dimensions.x || y + L::HEIGHT > tile.dimensions.y {
+                    mask &= Mask::from(
+                        (self.lane.x + Simd::splat(x.to_num())).simd_lt(bounds.x)
+                            & (self.lane.y + Simd::splat(y.to_num())).simd_lt(bounds.y),
+                    );
+                }
 
-                    state.pixel = Vec2 {
-                        x: i_vec % width_vec,
-                        y: i_vec / width_vec,
+                let point = Vec2 {
+                    x: self.depth_lane.x + Simd::splat(x as f32),
+                    y: self.depth_lane.y + Simd::splat(y as f32),
+                };
+
+                let mut coverage = Simd::splat(1.0);
+
+                let evaluate = |e: Edge<f32>| {
+                    Simd::splat(e.c) + Simd::splat(e.a) * point.x + Simd::splat(e.b) * point.y
+                };
+
+                if let Some((s1, s2, s3)) = planes.distance.filter(|_| mask.any()) {
+                    // outside of the triangle, the distance is to the closest edge segment
+                    // rather than to the line through it, which would stretch sharp corners
+                    let segment = |s: Segment| {
+                        let distance = evaluate(s.distance);
+                        let along = evaluate(s.along);
+                        let past = (-along)
+                            .simd_max(along - Simd::splat(s.length))
+                            .simd_max(Simd::splat(0.0));
+                        let outside = (distance * distance + past * past).sqrt();
+
+                        (
+                            distance,
+                            distance
+                                .simd_lt(Simd::splat(0.0))
+                                .select(outside, Simd::splat(f32::INFINITY)),
+                        )
                     };
+                    let (d1, o1) = segment(s1);
+                    let (d2, o2) = segment(s2);
+                    let (d3, o3) = segment(s3);
+                    let inside = d1.simd_min(d2).simd_min(d3);
+                    let distance = inside
+                        .simd_ge(Simd::splat(0.0))
+                        .select(inside, -o1.simd_min(o2).simd_min(o3));
 
-                    let x = NumberCast::<usize>::to_num(unsafe { state.pixel.x.transmute() }[0]);
-                    let y = NumberCast::<usize>::to_num(unsafe { state.pixel.y.transmute() }[0]);
+                    // pixels are covered from half a pixel outside of the triangle,
+                    // approximating their coverage with their distance to its boundary
+                    mask &= distance.simd_gt(Simd::splat(-0.5));
 
-                    let color = pixel(&state);
+                    if self.state.fill_mode == FillMode::Wireframe {
+                        mask &= distance.simd_le(Simd::splat(0.5));
+                    }
 
-                    color.store_select(&mut tile.dst[y * tile.dst_width + x..], mask.into());
+                    if self.state.antialias {
+                        coverage = (distance + Simd::splat(0.5))
+                            .simd_clamp(Simd::splat(0.0), Simd::splat(1.0));
+                    }
                 }
+
+                if mask.any() {
+                    let DepthPlane { z, dx, dy } = planes.depth;
+                    let (b1, b2, b3) = planes.barycentric;
+                    let state = SimdTrianglePixelState {
+                        pixel: Vec2 {
+                            x: origin.x + Simd::splat(x.to_num()),
+                            y: origin.y + Simd::splat(y.to_num()),
+                        },
+                        position: Vec2 {
+                            x: point.x + Simd::splat(tile.position.x as f32),
+                            y: point.y + Simd::splat(tile.position.y as f32),
+                        },
+                        depth: Simd::splat(z)
+                            + Simd::splat(dx) * point.x
+                            + Simd::splat(dy) * point.y,
+                        coverage,
+                        barycentric: Vec3 {
+                            x: evaluate(b1),
+                            y: evaluate(b2),
+                            z: evaluate(b3),
+                        },
+                        primitive,
+                        instance,
+                        front_facing: triangle.front_facing,
+                    };
+
+                    self.shade(tile, Vec2 { x, y }, mask, &state, pixel);
+                }
+            }
+        }
+    }
+
+    /// Shades the point `vertex`, the `index`th of the `primitive`th triangle of the list of the
+    /// `instance`th instance, at the given depth, covering the pixel it's in.
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    fn rasterize_point<const M: usize, V, R, F>(
+        &mut self,
+        tile: &mut Tile<'_, M>,
+        primitive: usize,
+        instance: usize,
+        index: usize,
+        vertex: V,
+        depth: f32,
+        front_facing: bool,
+        pixel: &F,
+    ) where
+        T: NumberCast<f64>,
+        V: RasterVertex<T>,
+        R: PixelResult<[Simd<u32, N>; M], Mask<i32, N>>,
+        F: Fn(&SimdTrianglePixelState<T, N>) -> R,
+    {
+        let position = vertex.position();
+        // pixels cover the area within half a pixel of their coordinates
+        let x = (position.x.to_num() + 0.5).floor() - tile.position.x as f64;
+        let y = (position.y.to_num() + 0.5).floor() - tile.position.y as f64;
+
+        if x < 0.0 || y < 0.0 || x >= tile.dimensions.x as f64 || y >= tile.dimensions.y as f64 {
+            return;
+        }
+
+        let (x, y) = (x as usize, y as usize);
+        let block = Vec2 {
+            x: x - x % L::WIDTH,
+            y: y - y % L::HEIGHT,
+        };
+        let mask = self.depth_lane.x.simd_eq(Simd::splat((x - block.x) as f32))
+            & self.depth_lane.y.simd_eq(Simd::splat((y - block.y) as f32));
+        let weight = |i: usize| Simd::splat(if i == index { 1.0 } else { 0.0 });
+
+        let state = SimdTrianglePixelState {
+            pixel: Vec2 {
+                x: self.lane.x + Simd::splat((tile.position.x + block.x).to_num()),
+                y: self.lane.y + Simd::splat((tile.position.y + block.y).to_num()),
+            },
+            position: Vec2 {
+                x: self.depth_lane.x + Simd::splat((tile.position.x + block.x) as f32),
+                y: self.depth_lane.y + Simd::splat((tile.position.y + block.y) as f32),
+            },
+            depth: Simd::splat(depth),
+            coverage: Simd::splat(1.0),
+            barycentric: Vec3 {
+                x: weight(0),
+                y: weight(1),
+                z: weight(2),
+            },
+            primitive,
+            instance,
+            front_facing,
+        };
+
+        self.shade(tile, block, mask, &state, pixel);
+    }
+
+    /// Shades the lanes of `mask` in the block at `block` relative to the tile, then depth tests
+    /// and stores those the pixel closure doesn't discard.
+    ///
+    /// Unless the closure may discard pixels, depth testing happens first so that occluded
+    /// pixels aren't shaded.
+    #[inline(always)]
+    fn shade<const M: usize, R, F>(
+        &mut self,
+        tile: &mut Tile<'_, M>,
+        block: Vec2<usize>,
+        mut mask: Mask<i32, N>,
+        state: &SimdTrianglePixelState<T, N>,
+        pixel: &F,
+    ) where
+        R: PixelResult<[Simd<u32, N>; M], Mask<i32, N>>,
+        F: Fn(&SimdTrianglePixelState<T, N>) -> R,
+    {
+        let index = L::indices(tile.dst_width);
+        let position = Vec2 {
+            x: tile.position.x + block.x,
+            y: tile.position.y + block.y,
+        };
+        let base = position.y * tile.dst_width + position.x;
+
+        let cell = match (&tile.depth, tile.hiz.as_deref()) {
+            (Some(_), Some(hiz)) => hiz
+                .cell(
+                    position,
+                    Vec2 {
+                        x: L::WIDTH,
+                        y: L::HEIGHT,
+                    },
+                )
+                .map(|cell| (hiz, cell)),
+            _ => None,
+        };
+
+        if let Some((hiz, cell)) = cell {
+            let min = mask.select(state.depth, Simd::splat(f32::INFINITY));
+            let max = mask.select(state.depth, Simd::splat(f32::NEG_INFINITY));
+            let range = (min.reduce_min(), max.reduce_max());
+
+            if self
+                .state
+                .depth
+                .compare
+                .rejects(range, (hiz.min[cell], hiz.max[cell]))
+            {
+                return;
             }
         }
+
+        let cell = cell.map(|(_, cell)| cell);
+
+        if !R::DISCARDS {
+            mask = self.depth_test(tile, position, cell, mask, state.depth);
+
+            if !mask.any() {
+                return;
+            }
+        }
+
+        let shaded = mask.to_bitmask().count_ones() as u64;
+
+        self.statistics.lanes_shaded += shaded;
+        self.statistics.lanes_masked += N as u64 - shaded;
+
+        let (colors, keep) = pixel(state).split();
+
+        if let Some(keep) = keep {
+            mask &= keep;
+        }
+
+        if R::DISCARDS {
+            mask = self.depth_test(tile, position, cell, mask, state.depth);
+        }
+
+        self.statistics.samples_passed += mask.to_bitmask().count_ones() as u64;
+
+        if let Some(fragments) = tile.fragments.as_deref_mut() {
+            let color = colors[0];
+            let mut bits = mask.to_bitmask();
+
+            while bits != 0 {
+                let lane = bits.trailing_zeros() as usize;
+
+                fragments.insert(
+                    base + index[lane],
+                    Fragment {
+                        color: color[lane],
+                        depth: state.depth[lane],
+                    },
+                );
+                bits &= bits - 1;
+            }
+
+            return;
+        }
+
+        for (target, color) in tile.targets.iter_mut().zip(colors) {
+            write::<N, L>(target, self.state.blend, base, index, mask, color);
+        }
+    }
+
+    /// Tests the lanes of `mask` in the block at `position` against the tile's depth target if
+    /// any, returning those that pass after writing their depth and updating the block `cell`
+    /// of the hierarchical-Z buffer.
+    #[inline(always)]
+    fn depth_test<const M: usize>(
+        &self,
+        tile: &mut Tile<'_, M>,
+        position: Vec2<usize>,
+        cell: Option<usize>,
+        mut mask: Mask<i32, N>,
+        src: Simd<f32, N>,
+    ) -> Mask<i32, N> {
+        let Some(dst) = tile.depth.as_deref_mut() else {
+            return mask;
+        };
+
+        let index = L::indices(tile.dst_width);
+        let base = position.y * tile.dst_width + position.x;
+        let depth = load::<_, N, L>(dst, base, index, mask);
+
+        mask &= test(self.state.depth.compare, src, depth);
+
+        if self.state.depth.write && mask.any() {
+            store::<_, N, L>(dst, base, index, mask, src);
+
+            if let (Some(hiz), Some(cell)) = (tile.hiz.as_deref_mut(), cell) {
+                // the whole block within the depth target, including pixels outside of the tile
+                let lanes = L::lanes::<usize>();
+                let inside = (lanes.x + Simd::splat(position.x))
+                    .simd_lt(Simd::splat(tile.dst_width))
+                    & (lanes.y + Simd::splat(position.y))
+                        .simd_lt(Simd::splat(dst.len() / tile.dst_width));
+                let inside = inside.cast();
+                let depth = load::<_, N, L>(dst, base, index, inside);
+
+                hiz.min[cell] = inside
+                    .select(depth, Simd::splat(f32::INFINITY))
+                    .reduce_min();
+                hiz.max[cell] = inside
+                    .select(depth, Simd::splat(f32::NEG_INFINITY))
+                    .reduce_max();
+            }
+        }
+
+        mask
+    }
+}
+
+impl<T, const N: usize, L> SimdTriangleRasterizer<T, N, L>
+where
+    LaneCount<N>: SupportedLaneCount,
+    T: SimdElement,
+    for<'a> Self: Rasterizer<
+        'a,
+        T,
+        State = SimdTrianglePixelState<T, N>,
+        Color = Simd<u32, N>,
+        Mask = Mask<i32, N>,
+    >,
+{
+    /// Rasterizes the first pass of visibility buffer rendering, writing the packed
+    /// [Visibility] of the closest triangle to every pixel instead of shading it.
+    ///
+    /// The tile should have a depth target, as the visibility of the last triangle drawn would
+    /// be written otherwise. Packed visibilities are written whole to the solid triangles,
+    /// whatever the blend mode, fill mode, antialiasing and write mask.
+    pub fn rasterize_visibility<V: RasterVertex<T>>(
+        &mut self,
+        mut tile: Tile<'_>,
+        list: &[V],
+        instance: u32,
+    ) {
+        let state = self.state;
+
+        self.state.blend = BlendMode::Replace;
+        self.state.fill_mode = FillMode::Solid;
+        self.state.antialias = false;
+        tile.targets[0].write_mask = ColorMask::ALL;
+
+        self.rasterize_instance(tile, list, instance as usize, |state| {
+            let visibility = Visibility {
+                primitive: state.primitive as u32,
+                instance: state.instance as u32,
+            };
+
+            Simd::splat(visibility.pack())
+        });
+
+        self.state = state;
+    }
+}
+
+/// Compares the depth of pixels against the one in the depth target.
+#[inline(always)]
+fn test<const N: usize>(compare: Compare, src: Simd<f32, N>, dst: Simd<f32, N>) -> Mask<i32, N>
+where
+    LaneCount<N>: SupportedLaneCount,
+{
+    match compare {
+        Compare::Never => Mask::splat(false),
+        Compare::Less => src.simd_lt(dst),
+        Compare::LessEqual => src.simd_le(dst),
+        Compare::Equal => src.simd_eq(dst),
+        Compare::GreaterEqual => src.simd_ge(dst),
+        Compare::Greater => src.simd_gt(dst),
+        Compare::NotEqual => src.simd_ne(dst),
+        Compare::Always => Mask::splat(true),
+    }
+}
+
+/// Writes the channels of `color` enabled by the target's write mask to the enabled lanes of the
+/// block at `base`.
+#[inline(always)]
+fn write<const N: usize, L>(
+    target: &mut RenderTarget<'_>,
+    mode: BlendMode,
+    base: usize,
+    index: Simd<usize, N>,
+    mask: Mask<i32, N>,
+    color: Simd<u32, N>,
+) where
+    LaneCount<N>: SupportedLaneCount,
+    L: LaneLayout<N>,
+{
+    match (mode, target.write_mask.bits()) {
+        (_, 0) => (),
+        (BlendMode::Replace, u32::MAX) => store::<_, N, L>(target.dst, base, index, mask, color),
+        (mode, bits) => {
+            let bits = Simd::splat(bits);
+            let dst = load::<_, N, L>(target.dst, base, index, mask);
+            let color = blend(mode, color, dst);
+
+            store::<_, N, L>(target.dst, base, index, mask, dst & !bits | color & bits);
+        }
+    }
+}
+
+/// Combines the colors `src` and `dst` of every lane according to `mode`.
+#[inline(always)]
+fn blend<const N: usize>(mode: BlendMode, src: Simd<u32, N>, dst: Simd<u32, N>) -> Simd<u32, N>
+where
+    LaneCount<N>: SupportedLaneCount,
+{
+    let max = Simd::splat(0xff);
+    let alpha = src >> Simd::splat(24);
+    let channel = |shift: u32| {
+        let amount = Simd::splat(shift);
+        let src = (src >> amount) & max;
+        let dst = (dst >> amount) & max;
+        let weighted =
+            |weight: Simd<u32, N>| (src * weight + dst * (max - alpha) + Simd::splat(127)) / max;
+
+        let channel = match mode {
+            BlendMode::Replace => src,
+            BlendMode::Alpha if shift != 24 => weighted(alpha),
+            BlendMode::Alpha | BlendMode::Premultiplied => weighted(max).simd_min(max),
+            BlendMode::Additive => (src + dst).simd_min(max),
+        };
+
+        channel << amount
+    };
+
+    channel(24) | channel(16) | channel(8) | channel(0)
+}
+
+/// Reads the enabled lanes of the block at `base`, the others being zero.
+#[inline(always)]
+fn load<U, const N: usize, L>(
+    src: &[U],
+    base: usize,
+    index: Simd<usize, N>,
+    mask: Mask<U::Mask, N>,
+) -> Simd<U, N>
+where
+    LaneCount<N>: SupportedLaneCount,
+    U: SimdElement + Default,
+    L: LaneLayout<N>,
+{
+    if L::HEIGHT == 1 {
+        Simd::load_select_or_default(&src[base..], mask)
+    } else {
+        Simd::gather_select(src, mask.cast(), index + Simd::splat(base), Simd::default())
+    }
+}
+
+/// Writes the enabled lanes of `value` to the block at `base`, using a contiguous store when
+/// the layout is a single row and a scatter otherwise.
+#[inline(always)]
+fn store<U, const N: usize, L>(
+    dst: &mut [U],
+    base: usize,
+    index: Simd<usize, N>,
+    mask: Mask<U::Mask, N>,
+    value: Simd<U, N>,
+) where
+    LaneCount<N>: SupportedLaneCount,
+    U: SimdElement,
+    L: LaneLayout<N>,
+{
+    if L::HEIGHT == 1 {
+        value.store_select(&mut dst[base..], mask);
+    } else {
+        value.scatter_select(dst, mask.cast(), index + Simd::splat(base));
     }
 }
@@ -0,0 +1,373 @@
+use std::{
+    mem,
+    simd::{LaneCount, Mask, Simd, SimdElement, SupportedLaneCount},
+};
+
+use crate::raster::{PixelResult, RasterVertex, Rasterizer, RasterizerState, Statistics, Tile};
+
+use super::{layout::RowLayout, SimdTrianglePixelState, SimdTriangleRasterizer};
+
+/// SIMD instruction set the lane count of a [DispatchTriangleRasterizer] is picked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimdLevel {
+    /// 128-bit vectors, without any extension known to the dispatcher.
+    Fallback,
+    Sse2,
+    Avx2,
+    Avx512,
+    Neon,
+}
+
+/// Pixel function for a [DispatchTriangleRasterizer].
+///
+/// Unlike the closure taken by [Rasterizer::rasterize], it has to be generic over the lane
+/// count, as `N` is only known once the dispatcher has picked a kernel.
+///
+/// `M` is the number of color targets of the tiles it's drawn to.
+pub trait PixelKernel<T: SimdElement, const M: usize = 1> {
+    /// Value returned by [pixel](Self::pixel), see [PixelResult].
+    type Output<const N: usize>: PixelResult<[Simd<u32, N>; M], Mask<i32, N>>
+    where
+        LaneCount<N>: SupportedLaneCount;
+
+    fn pixel<const N: usize>(&self, state: &SimdTrianglePixelState<T, N>) -> Self::Output<N>
+    where
+        LaneCount<N>: SupportedLaneCount;
+}
+
+/// Triangle rasterizer picking its lane count at runtime from the SIMD extensions supported by
+/// the CPU, so that a single binary can make use of the widest vectors available.
+///
+/// It only rasterizes raw triangle lists with a [PixelKernel]. It doesn't implement
+/// [Rasterizer], whose pixel closures have a single lane count, so it can't draw through a
+/// [Pipeline](crate::pipeline::Pipeline).
+#[derive(Debug)]
+pub struct DispatchTriangleRasterizer<T, L = RowLayout>
+where
+    T: SimdElement,
+{
+    pub state: RasterizerState,
+    pub statistics: Statistics,
+    level: SimdLevel,
+    x2: SimdTriangleRasterizer<T, 2, L>,
+    x4: SimdTriangleRasterizer<T, 4, L>,
+    x8: SimdTriangleRasterizer<T, 8, L>,
+    x16: SimdTriangleRasterizer<T, 16, L>,
+    x32: SimdTriangleRasterizer<T, 32, L>,
+}
+
+impl SimdLevel {
+    /// Returns the widest SIMD extension supported by the running CPU.
+    pub fn detect() -> Self {
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        {
+            if is_x86_feature_detected!("avx512f") {
+                return Self::Avx512;
+            }
+            if is_x86_feature_detected!("avx2") {
+                return Self::Avx2;
+            }
+            if is_x86_feature_detected!("sse2") {
+                return Self::Sse2;
+            }
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        {
+            if std::arch::is_aarch64_feature_detected!("neon") {
+                return Self::Neon;
+            }
+        }
+
+        Self::Fallback
+    }
+
+    /// Returns whether the running CPU supports this extension.
+    pub fn is_supported(self) -> bool {
+        match self {
+            Self::Fallback => true,
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Sse2 => is_x86_feature_detected!("sse2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+            Self::Avx512 => is_x86_feature_detected!("avx512f"),
+            #[cfg(target_arch = "aarch64")]
+            Self::Neon => std::arch::is_aarch64_feature_detected!("neon"),
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    /// Returns the width of a vector register in bits.
+    pub const fn register_bits(self) -> usize {
+        match self {
+            Self::Fallback | Self::Sse2 | Self::Neon => 128,
+            Self::Avx2 => 256,
+            Self::Avx512 => 512,
+        }
+    }
+
+    /// Returns the number of `T` lanes filling a vector register, between 2 and 32.
+    pub const fn lanes<T>(self) -> usize {
+        let lanes = self.register_bits() / 8 / mem::size_of::<T>();
+
+        if lanes < 2 {
+            2
+        } else if lanes > 32 {
+            32
+        } else {
+            lanes
+        }
+    }
+}
+
+impl Default for SimdLevel {
+    fn default() -> Self {
+        Self::detect()
+    }
+}
+
+impl<T, L> DispatchTriangleRasterizer<T, L>
+where
+    T: SimdElement,
+    SimdTriangleRasterizer<T, 2, L>: Default,
+    SimdTriangleRasterizer<T, 4, L>: Default,
+    SimdTriangleRasterizer<T, 8, L>: Default,
+    SimdTriangleRasterizer<T, 16, L>: Default,
+    SimdTriangleRasterizer<T, 32, L>: Default,
+{
+    /// Creates a rasterizer for the given level rather than the detected one, e.g. to test every
+    /// kernel on a single machine.
+    ///
+    /// Levels the CPU doesn't support still pick their lane count, but run without the
+    /// corresponding target features enabled.
+    pub fn with_level(level: SimdLevel) -> Self {
+        Self {
+            state: Default::default(),
+            statistics: Default::default(),
+            level,
+            x2: Default::default(),
+            x4: Default::default(),
+            x8: Default::default(),
+            x16: Default::default(),
+            x32: Default::default(),
+        }
+    }
+}
+
+impl<T, L> Default for DispatchTriangleRasterizer<T, L>
+where
+    T: SimdElement,
+    SimdTriangleRasterizer<T, 2, L>: Default,
+    SimdTriangleRasterizer<T, 4, L>: Default,
+    SimdTriangleRasterizer<T, 8, L>: Default,
+    SimdTriangleRasterizer<T, 16, L>: Default,
+    SimdTriangleRasterizer<T, 32, L>: Default,
+{
+    fn default() -> Self {
+        Self::with_level(SimdLevel::detect())
+    }
+}
+
+impl<T, L> DispatchTriangleRasterizer<T, L>
+where
+    T: SimdElement,
+{
+    pub fn level(&self) -> SimdLevel {
+        self.level
+    }
+
+    /// Returns the lane count of the kernel picked for the level.
+    pub fn lanes(&self) -> usize {
+        self.level.lanes::<T>()
+    }
+}
+
+impl<T, L> DispatchTriangleRasterizer<T, L>
+where
+    T: SimdElement,
+    for<'a> SimdTriangleRasterizer<T, 2, L>: Rasterizer<
+        'a,
+        T,
+        State = SimdTrianglePixelState<T, 2>,
+        Color = Simd<u32, 2>,
+        Mask = Mask<i32, 2>,
+    >,
+    for<'a> SimdTriangleRasterizer<T, 4, L>: Rasterizer<
+        'a,
+        T,
+        State = SimdTrianglePixelState<T, 4>,
+        Color = Simd<u32, 4>,
+        Mask = Mask<i32, 4>,
+    >,
+    for<'a> SimdTriangleRasterizer<T, 8, L>: Rasterizer<
+        'a,
+        T,
+        State = SimdTrianglePixelState<T, 8>,
+        Color = Simd<u32, 8>,
+        Mask = Mask<i32, 8>,
+    >,
+    for<'a> SimdTriangleRasterizer<T, 16, L>: Rasterizer<
+        'a,
+        T,
+        State = SimdTrianglePixelState<T, 16>,
+        Color = Simd<u32, 16>,
+        Mask = Mask<i32, 16>,
+    >,
+    for<'a> SimdTriangleRasterizer<T, 32, L>: Rasterizer<
+        'a,
+        T,
+        State = SimdTrianglePixelState<T, 32>,
+        Color = Simd<u32, 32>,
+        Mask = Mask<i32, 32>,
+    >,
+{
+    /// Draws the triangles of `list` like [Rasterizer::rasterize], with the kernel of the
+    /// picked lane count.
+    pub fn rasterize<const M: usize, V: RasterVertex<T>, K: PixelKernel<T, M>>(
+        &mut self,
+        tile: Tile<'_, M>,
+        list: &[V],
+        kernel: &K,
+    ) {
+        let level = self.level;
+
+        match self.lanes() {
+            2 => {
+                self.x2.state = self.state;
+                self.x2.statistics = self.statistics;
+                dispatch(level, &mut self.x2, tile, list, kernel);
+                self.statistics = self.x2.statistics;
+            }
+            4 => {
+                self.x4.state = self.state;
+                self.x4.statistics = self.statistics;
+                dispatch(level, &mut self.x4, tile, list, kernel);
+                self.statistics = self.x4.statistics;
+            }
+            8 => {
+                self.x8.state = self.state;
+                self.x8.statistics = self.statistics;
+                dispatch(level, &mut self.x8, tile, list, kernel);
+                self.statistics = self.x8.statistics;
+            }
+            16 => {
+                self.x16.state = self.state;
+                self.x16.statistics = self.statistics;
+                dispatch(level, &mut self.x16, tile, list, kernel);
+                self.statistics = self.x16.statistics;
+            }
+            _ => {
+                self.x32.state = self.state;
+                self.x32.statistics = self.statistics;
+                dispatch(level, &mut self.x32, tile, list, kernel);
+                self.statistics = self.x32.statistics;
+            }
+        }
+    }
+}
+
+#[inline(always)]
+fn dispatch<T, const N: usize, const M: usize, R, V, K>(
+    level: SimdLevel,
+    rast: &mut R,
+    tile: Tile<'_, M>,
+    list: &[V],
+    kernel: &K,
+) where
+    LaneCount<N>: SupportedLaneCount,
+    T: SimdElement,
+    for<'a> R: Rasterizer<
+        'a,
+        T,
+        State = SimdTrianglePixelState<T, N>,
+        Color = Simd<u32, N>,
+        Mask = Mask<i32, N>,
+    >,
+    V: RasterVertex<T>,
+    K: PixelKernel<T, M>,
+{
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    if level.is_supported() {
+        match level {
+            // Safety: the CPU was just checked to support the target features
+            SimdLevel::Avx512 => return unsafe { rasterize_avx512(rast, tile, list, kernel) },
+            SimdLevel::Avx2 => return unsafe { rasterize_avx2(rast, tile, list, kernel) },
+            _ => (),
+        }
+    }
+
+    #[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+    let _ = level;
+
+    rasterize(rast, tile, list, kernel)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx2")]
+unsafe fn rasterize_avx2<T, const N: usize, const M: usize, R, V, K>(
+    rast: &mut R,
+    tile: Tile<'_, M>,
+    list: &[V],
+    kernel: &K,
+) where
+    LaneCount<N>: SupportedLaneCount,
+    T: SimdElement,
+    for<'a> R: Rasterizer<
+        'a,
+        T,
+        State = SimdTrianglePixelState<T, N>,
+        Color = Simd<u32, N>,
+        Mask = Mask<i32, N>,
+    >,
+    V: RasterVertex<T>,
+    K: PixelKernel<T, M>,
+{
+    rasterize(rast, tile, list, kernel)
+}
+
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+#[target_feature(enable = "avx512f")]
+unsafe fn rasterize_avx512<T, const N: usize, const M: usize, R, V, K>(
+    rast: &mut R,
+    tile: Tile<'_, M>,
+    list: &[V],
+    kernel: &K,
+) where
+    LaneCount<N>: SupportedLaneCount,
+    T: SimdElement,
+    for<'a> R: Rasterizer<
+        'a,
+        T,
+        State = SimdTrianglePixelState<T, N>,
+        Color = Simd<u32, N>,
+        Mask = Mask<i32, N>,
+    >,
+    V: RasterVertex<T>,
+    K: PixelKernel<T, M>,
+{
+    rasterize(rast, tile, list, kernel)
+}
+
+#[inline(always)]
+fn rasterize<T, const N: usize, const M: usize, R, V, K>(
+    rast: &mut R,
+    tile: Tile<'_, M>,
+    list: &[V],
+    kernel: &K,
+) where
+    LaneCount<N>: SupportedLaneCount,
+    T: SimdElement,
+    for<'a> R: Rasterizer<
+        'a,
+        T,
+        State = SimdTrianglePixelState<T, N>,
+        Color = Simd<u32, N>,
+        Mask = Mask<i32, N>,
+    >,
+    V: RasterVertex<T>,
+    K: PixelKernel<T, M>,
+{
+    rast.rasterize(tile, list, |state| kernel.pixel(state))
+}
@@ -0,0 +1,66 @@
+use std::{
+    array,
+    simd::{LaneCount, Simd, SimdElement, SupportedLaneCount},
+};
+
+use crate::{math::Vec2, NumberCast};
+
+/// Describes how the `N` lanes of a SIMD vector map to the pixels of a tile.
+///
+/// Lanes are laid out row-major inside a block of `WIDTH` x `HEIGHT` pixels,
+/// which is the unit the rasterizer steps its edge functions by.
+pub trait LaneLayout<const N: usize> {
+    const WIDTH: usize;
+    const HEIGHT: usize;
+
+    /// Returns the offset of every lane from the top-left corner of its block.
+    #[inline(always)]
+    fn lanes<T>() -> Vec2<Simd<T, N>>
+    where
+        LaneCount<N>: SupportedLaneCount,
+        T: SimdElement,
+        usize: NumberCast<T>,
+    {
+        Vec2 {
+            x: Simd::from_array(array::from_fn(|i| (i % Self::WIDTH).to_num())),
+            y: Simd::from_array(array::from_fn(|i| (i / Self::WIDTH).to_num())),
+        }
+    }
+
+    /// Returns the offset of every lane into a destination `dst_width` pixels wide.
+    #[inline(always)]
+    fn indices(dst_width: usize) -> Simd<usize, N>
+    where
+        LaneCount<N>: SupportedLaneCount,
+    {
+        Simd::from_array(array::from_fn(|i| {
+            (i / Self::WIDTH) * dst_width + i % Self::WIDTH
+        }))
+    }
+}
+
+/// Maps the lanes to a horizontal run of `N` pixels.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RowLayout;
+
+/// Maps the lanes to a block as close to a square as `N` allows, e.g. 8x8 for
+/// `N = 64`, 8x4 for `N = 32` and 4x4 for `N = 16`.
+///
+/// Thin and vertical triangles waste far fewer lanes than with [RowLayout],
+/// at the cost of scattered stores.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BlockLayout;
+
+impl<const N: usize> LaneLayout<N> for RowLayout {
+    const WIDTH: usize = N;
+    const HEIGHT: usize = 1;
+}
+
+impl<const N: usize> LaneLayout<N> for BlockLayout {
+    const WIDTH: usize = block_width(N);
+    const HEIGHT: usize = N / block_width(N);
+}
+
+const fn block_width(n: usize) -> usize {
+    1 << n.ilog2().div_ceil(2)
+}
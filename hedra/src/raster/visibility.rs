@@ -0,0 +1,70 @@
+//! Visibility buffer rendering, which splits drawing into two passes.
+//!
+//! The first pass only rasterizes and depth tests triangles, writing the packed [Visibility] of
+//! the closest triangle to every pixel of a `u32` target. The second pass, [resolve], then
+//! shades every covered pixel exactly once regardless of overdraw, reconstructing barycentrics
+//! with [Cartesian::to_barycentric](crate::math::Cartesian::to_barycentric) from the vertices
+//! of the visible triangle.
+
+use crate::math::Vec2;
+
+use super::Tile;
+
+/// Triangle and instance visible at a pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Visibility {
+    /// Index of the triangle in the list it was rasterized from.
+    pub primitive: u32,
+    pub instance: u32,
+}
+
+impl Visibility {
+    /// Number of low bits of a packed visibility holding the primitive index, the remaining
+    /// ones holding the instance index.
+    pub const PRIMITIVE_BITS: u32 = 20;
+
+    /// Packed value of pixels no triangle is visible at, which visibility targets have to be
+    /// cleared to.
+    pub const NONE: u32 = u32::MAX;
+
+    #[inline(always)]
+    pub fn pack(self) -> u32 {
+        debug_assert!(self.primitive < 1 << Self::PRIMITIVE_BITS);
+        debug_assert!(self.instance < (1 << (32 - Self::PRIMITIVE_BITS)) - 1);
+
+        self.instance << Self::PRIMITIVE_BITS | self.primitive
+    }
+
+    #[inline(always)]
+    pub fn unpack(packed: u32) -> Option<Self> {
+        (packed != Self::NONE).then_some(Self {
+            primitive: packed & ((1 << Self::PRIMITIVE_BITS) - 1),
+            instance: packed >> Self::PRIMITIVE_BITS,
+        })
+    }
+}
+
+/// Shades the pixels of `tile` from a visibility target with the same layout as its destination,
+/// calling `shade` once for every pixel some triangle is visible at.
+///
+/// Pixels no triangle is visible at are left untouched.
+pub fn resolve<F: FnMut(Vec2<usize>, Visibility) -> u32>(
+    visibility: &[u32],
+    tile: Tile<'_>,
+    mut shade: F,
+) {
+    let [target] = tile.targets;
+    let bits = target.write_mask.bits();
+
+    for y in tile.position.y..tile.position.y + tile.dimensions.y {
+        let row = y * tile.dst_width;
+
+        for x in tile.position.x..tile.position.x + tile.dimensions.x {
+            if let Some(id) = Visibility::unpack(visibility[row + x]) {
+                let dst = &mut target.dst[row + x];
+
+                *dst = *dst & !bits | shade(Vec2 { x, y }, id) & bits;
+            }
+        }
+    }
+}
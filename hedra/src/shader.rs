@@ -1,6 +1,42 @@
+#[cfg(feature = "simd")]
+use std::{
+    array,
+    simd::{LaneCount, Simd, SimdElement, SupportedLaneCount},
+};
+
+#[cfg(feature = "simd")]
+use crate::math::{Vec2, Vec3, Vec4};
+
+#[cfg(feature = "simd")]
+macro_rules! lanes_impl {
+    ($v:ident { $($field:ident),* }) => {
+        impl<T, const N: usize> Lanes<N> for $v<Simd<T, N>>
+        where
+            LaneCount<N>: SupportedLaneCount,
+            T: SimdElement,
+        {
+            type Lane = $v<T>;
+
+            #[inline(always)]
+            fn from_lanes(lanes: [Self::Lane; N]) -> Self {
+                $v {
+                    $($field: Simd::from_array(lanes.map(|lane| lane.$field))),*
+                }
+            }
+
+            #[inline(always)]
+            fn to_lanes(self) -> [Self::Lane; N] {
+                array::from_fn(|i| $v {
+                    $($field: self.$field[i]),*
+                })
+            }
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! shader_params {
-    (struct $t:tt $($(@$attr:tt)? $field:ident:$type:tt),*($($vis:tt)*)) => {
+    (struct $t:tt $($(@$attr:tt)? $field:ident:$type:ty),* $(,)? [$($vis:tt)*]) => {
         #[derive(Default)]
         $($vis)* struct $t { $(pub $field: $type),* }
     };
@@ -18,39 +54,84 @@ macro_rules! shader_params {
         }
     };
 
-    (impl $t:tt<$state_arg:ident:$state:ty>{$($body:tt)*}@vertex_index $field:ident:$type:tt $($tail:tt)*) => {
-        use $crate::pipeline::VertexIndex;
-        shader_params!(impl $t<$state_arg: $state> {
-            $field: $state_arg.get_vertex_index() as $type,
+    (impl $t:tt<$state_arg:ident:$state:ty>{$($body:tt)*}@vertex_index $field:ident:$type:ty $(,$($tail:tt)*)?) => {
+        $crate::shader_params!(impl $t<$state_arg: $state> {
+            $field: { use $crate::pipeline::VertexState as _; $state_arg.get_vertex_index() } as $type,
+            $($body)*
+        } $($($tail)*)?);
+    };
+
+    (impl $t:tt<$state_arg:ident:$state:ty>{$($body:tt)*}@instance_index $field:ident:$type:ty $(,$($tail:tt)*)?) => {
+        $crate::shader_params!(impl $t<$state_arg: $state> {
+            $field: {
+                // vertex and pixel states both have an instance index
+                use $crate::pipeline::{PixelState as _, VertexState as _};
+                $state_arg.get_instance_index()
+            } as $type,
+            $($body)*
+        } $($($tail)*)?);
+    };
+
+    (impl $t:tt<$state_arg:ident:$state:ty>{$($body:tt)*}@pixel $field:ident:$type:ty $(,$($tail:tt)*)?) => {
+        $crate::shader_params!(impl $t<$state_arg: $state> {
+            $field: { use $crate::pipeline::PixelState as _; $state_arg.get_pixel() },
+            $($body)*
+        } $($($tail)*)?);
+    };
+
+    (impl $t:tt<$state_arg:ident:$state:ty>{$($body:tt)*}@frag_coord $field:ident:$type:ty $(,$($tail:tt)*)?) => {
+        $crate::shader_params!(impl $t<$state_arg: $state> {
+            $field: { use $crate::pipeline::PixelState as _; $state_arg.get_frag_coord() },
             $($body)*
-        } $($tail)*);
+        } $($($tail)*)?);
     };
 
-    (impl $t:tt<$state_arg:ident:$state:ty>{$($body:tt)*}@pixel $field:ident:$type:tt $($tail:tt)*) => {
-        use $crate::pipeline::PixelState;
-        shader_params!(impl $t<$state_arg: $state> {
-            $field: $state_arg.get_pixel(),
+    (impl $t:tt<$state_arg:ident:$state:ty>{$($body:tt)*}@barycentric $field:ident:$type:ty $(,$($tail:tt)*)?) => {
+        $crate::shader_params!(impl $t<$state_arg: $state> {
+            $field: { use $crate::pipeline::PixelState as _; $state_arg.get_barycentric() },
             $($body)*
-        } $($tail)*);
+        } $($($tail)*)?);
     };
 
-    (impl $t:tt<$state:ty> {$($body:tt)*} $field:ident:$type:tt $($tail:tt)*) => {
-        shader_params!(impl $t<state: $state> { $($body)* } $($tail)*);
+    (impl $t:tt<$state_arg:ident:$state:ty>{$($body:tt)*}@primitive_index $field:ident:$type:ty $(,$($tail:tt)*)?) => {
+        $crate::shader_params!(impl $t<$state_arg: $state> {
+            $field: { use $crate::pipeline::PixelState as _; $state_arg.get_primitive_index() } as $type,
+            $($body)*
+        } $($($tail)*)?);
+    };
+
+    (impl $t:tt<$state_arg:ident:$state:ty>{$($body:tt)*}@front_facing $field:ident:$type:ty $(,$($tail:tt)*)?) => {
+        $crate::shader_params!(impl $t<$state_arg: $state> {
+            $field: { use $crate::pipeline::PixelState as _; $state_arg.get_front_facing() } as $type,
+            $($body)*
+        } $($($tail)*)?);
+    };
+
+    (impl $t:tt<$state_arg:ident:$state:ty>{$($body:tt)*}@sample_index $field:ident:$type:ty $(,$($tail:tt)*)?) => {
+        $crate::shader_params!(impl $t<$state_arg: $state> {
+            $field: { use $crate::pipeline::PixelState as _; $state_arg.get_sample_index() } as $type,
+            $($body)*
+        } $($($tail)*)?);
+    };
+
+    // fields without a builtin keep their default value
+    (impl $t:tt<$state_arg:ident:$state:ty>{$($body:tt)*} $field:ident:$type:ty $(,$($tail:tt)*)?) => {
+        $crate::shader_params!(impl $t<$state_arg: $state> { $($body)* } $($($tail)*)?);
     };
 
     ($t:tt<$state:ty>{$($x:tt)*}) => {
-        shader_params!(struct $t $($x)* ());
-        shader_params!(impl $t<state: $state> {} $($x)*);
+        $crate::shader_params!(struct $t $($x)* []);
+        $crate::shader_params!(impl $t<state: $state> {} $($x)*);
     };
 
     (pub $t:tt<$state:ty>{$($x:tt)*}) => {
-        shader_params!(struct $t $($x)* (pub));
-        shader_params!(impl $t<state: $state> {} $($x)*);
+        $crate::shader_params!(struct $t $($x)* [pub]);
+        $crate::shader_params!(impl $t<state: $state> {} $($x)*);
     };
 
     (pub($($vis:tt)+) $t:tt<$state:ty>{$($x:tt)*}) => {
-        shader_params!(struct $t $($x)* (pub($($vis)+)));
-        shader_params!(impl $t<$state> {} $($x)*);
+        $crate::shader_params!(struct $t $($x)* [pub($($vis)+)]);
+        $crate::shader_params!(impl $t<state: $state> {} $($x)*);
     };
 }
 
@@ -62,16 +143,66 @@ pub enum Interpolation<T> {
     // Perspective(T),
 }
 
+/// Vertex shader, whose fields hold the uniforms shared by every vertex of a draw.
 pub trait VertexShader<T> {
     type VertexInput;
     type VertexOutput;
 
-    fn vertex(input: Self::VertexInput) -> Self::VertexOutput;
+    fn vertex(&self, input: Self::VertexInput) -> Self::VertexOutput;
 }
 
+/// Pixel shader, whose fields hold the uniforms shared by every pixel of a draw.
 pub trait PixelShader<T> {
     type PixelInput;
     type PixelOutput;
 
-    fn pixel(input: Self::PixelInput) -> Self::PixelOutput;
+    fn pixel(&self, input: Self::PixelInput) -> Self::PixelOutput;
+}
+
+/// Vertex shader running on `N` vertices at once, their inputs and outputs laid out as
+/// structures of arrays the same way pixels are.
+#[cfg(feature = "simd")]
+pub trait SimdVertexShader<T, const N: usize>
+where
+    LaneCount<N>: SupportedLaneCount,
+{
+    type VertexInput: Lanes<N>;
+    type VertexOutput: Lanes<N>;
+
+    fn vertex(&self, input: Self::VertexInput) -> Self::VertexOutput;
+}
+
+/// Structure of arrays holding a value of type [Lanes::Lane] in each of its `N` lanes.
+#[cfg(feature = "simd")]
+pub trait Lanes<const N: usize> {
+    type Lane;
+
+    fn from_lanes(lanes: [Self::Lane; N]) -> Self;
+    fn to_lanes(self) -> [Self::Lane; N];
 }
+
+#[cfg(feature = "simd")]
+impl<T, const N: usize> Lanes<N> for Simd<T, N>
+where
+    LaneCount<N>: SupportedLaneCount,
+    T: SimdElement,
+{
+    type Lane = T;
+
+    #[inline(always)]
+    fn from_lanes(lanes: [Self::Lane; N]) -> Self {
+        Simd::from_array(lanes)
+    }
+
+    #[inline(always)]
+    fn to_lanes(self) -> [Self::Lane; N] {
+        self.to_array()
+    }
+}
+
+#[cfg(feature = "simd")]
+lanes_impl!(Vec2 { x, y });
+#[cfg(feature = "simd")]
+lanes_impl!(Vec3 { x, y, z });
+#[cfg(feature = "simd")]
+lanes_impl!(Vec4 { x, y, z, w });
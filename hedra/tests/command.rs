@@ -0,0 +1,183 @@
+#![feature(portable_simd)]
+
+use std::simd::Simd;
+
+use hedra::{
+    math::{Vec2, Vec3},
+    pipeline::{
+        command::CommandList,
+        state::PipelineState,
+        vertex::{
+            Attributes, FetchVertex, FetchedVertex, Format, StepMode, VertexAttribute,
+            VertexBuffer, VertexBufferLayout, VertexLayout,
+        },
+        VertexState,
+    },
+    raster::{
+        simd::{SimdTrianglePixelState, SimdTriangleRasterizer},
+        RenderTarget, Tile,
+    },
+    shader::{PixelShader, VertexShader},
+};
+
+type Rasterizer = SimdTriangleRasterizer<f32, 8>;
+
+const SIZE: usize = 32;
+
+/// Vertex with a float position.
+struct Float(Vec2<f32>);
+
+/// Vertex with an integer position, drawn with a different shader.
+struct Integer(Vec2<u16>);
+
+struct Pixel;
+
+struct FloatShader;
+
+struct IntegerShader {
+    color: u32,
+}
+
+impl FetchVertex for Float {
+    fn fetch(attributes: &mut Attributes<'_>) -> Self {
+        Self(attributes.read())
+    }
+}
+
+impl FetchVertex for Integer {
+    fn fetch(attributes: &mut Attributes<'_>) -> Self {
+        Self(attributes.read())
+    }
+}
+
+impl From<FetchedVertex<'_, Float>> for Float {
+    fn from(state: FetchedVertex<'_, Float>) -> Self {
+        state.get_vertex()
+    }
+}
+
+impl From<FetchedVertex<'_, Integer>> for Integer {
+    fn from(state: FetchedVertex<'_, Integer>) -> Self {
+        state.get_vertex()
+    }
+}
+
+impl From<&SimdTrianglePixelState<f32, 8>> for Pixel {
+    fn from(_: &SimdTrianglePixelState<f32, 8>) -> Self {
+        Self
+    }
+}
+
+impl VertexShader<f32> for FloatShader {
+    type VertexInput = Float;
+    type VertexOutput = Vec3<f32>;
+
+    fn vertex(&self, Float(position): Float) -> Vec3<f32> {
+        Vec3 {
+            x: position.x,
+            y: position.y,
+            z: 0.0,
+        }
+    }
+}
+
+impl PixelShader<f32> for FloatShader {
+    type PixelInput = Pixel;
+    type PixelOutput = Simd<u32, 8>;
+
+    fn pixel(&self, _: Pixel) -> Simd<u32, 8> {
+        Simd::splat(1)
+    }
+}
+
+impl VertexShader<f32> for IntegerShader {
+    type VertexInput = Integer;
+    type VertexOutput = Vec3<f32>;
+
+    fn vertex(&self, Integer(position): Integer) -> Vec3<f32> {
+        Vec3 {
+            x: position.x as f32,
+            y: position.y as f32,
+            z: 0.0,
+        }
+    }
+}
+
+impl PixelShader<f32> for IntegerShader {
+    type PixelInput = Pixel;
+    type PixelOutput = Simd<u32, 8>;
+
+    fn pixel(&self, _: Pixel) -> Simd<u32, 8> {
+        Simd::splat(self.color)
+    }
+}
+
+/// Returns the layout and vertices of an 8 pixels wide quad at `x`, with components stored in
+/// `format` as encoded by `bytes`.
+fn quad(x: u16, format: Format, bytes: fn(u16) -> Vec<u8>) -> (VertexLayout, Vec<u8>) {
+    let size = format.size();
+    let layout = VertexLayout {
+        buffers: vec![VertexBufferLayout {
+            stride: 2 * size,
+            step: StepMode::Vertex,
+        }],
+        attributes: vec![VertexAttribute {
+            buffer: 0,
+            offset: 0,
+            format,
+            components: 2,
+        }],
+    };
+    let vertices = [(0, 0), (8, 0), (0, 8), (8, 0), (8, 8), (0, 8)]
+        .into_iter()
+        .flat_map(|(dx, y)| [x + dx, y])
+        .flat_map(bytes)
+        .collect();
+
+    (layout, vertices)
+}
+
+fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+#[test]
+fn mixed_draws() {
+    let (float_layout, float) = quad(0, Format::F32, |v| (v as f32).to_le_bytes().to_vec());
+    let (integer_layout, integer) = quad(16, Format::U16, |v| v.to_le_bytes().to_vec());
+    let float_pipeline = PipelineState::builder(FloatShader)
+        .layout(float_layout)
+        .build()
+        .unwrap();
+    let integer_pipeline = PipelineState::builder(IntegerShader { color: 2 })
+        .layout(integer_layout)
+        .build()
+        .unwrap();
+    let float: [&dyn VertexBuffer; 1] = [&float];
+    let integer: [&dyn VertexBuffer; 1] = [&integer];
+    let mut list = CommandList::<f32, Rasterizer>::new();
+
+    list.clear(0xff);
+    list.draw::<FloatShader, Float>(&float_pipeline, &float, 6);
+    list.draw::<IntegerShader, Integer>(&integer_pipeline, &integer, 6);
+
+    assert_send_sync(&list);
+
+    let mut dst = vec![0; SIZE * SIZE];
+    let mut rasterizer = Rasterizer::default();
+
+    list.clone().replay(
+        &mut rasterizer,
+        &mut [Tile {
+            targets: [RenderTarget::new(&mut dst)],
+            depth: None,
+            hiz: None,
+            fragments: None,
+            dst_width: SIZE,
+            position: Vec2 { x: 0, y: 0 },
+            dimensions: Vec2 { x: SIZE, y: SIZE },
+        }],
+    );
+
+    assert_eq!(dst[4 * SIZE + 4], 1);
+    assert_eq!(dst[4 * SIZE + 20], 2);
+    assert_eq!(dst[4 * SIZE + 12], 0xff);
+}
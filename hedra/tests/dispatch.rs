@@ -0,0 +1,93 @@
+#![feature(portable_simd)]
+
+use std::simd::{num::SimdInt, LaneCount, Simd, SupportedLaneCount};
+
+use hedra::{
+    math::Vec2,
+    raster::{
+        simd::{
+            dispatch::{DispatchTriangleRasterizer, PixelKernel, SimdLevel},
+            layout::BlockLayout,
+            SimdTrianglePixelState, SimdTriangleRasterizer,
+        },
+        Rasterizer, RenderTarget, Tile,
+    },
+};
+
+const SIZE: usize = 100;
+
+const TRIANGLE: [Vec2<i32>; 3] = [
+    Vec2 { x: 0, y: 0 },
+    Vec2 { x: 90, y: 10 },
+    Vec2 { x: 30, y: 95 },
+];
+
+/// Colors pixels with their coordinates.
+struct Coordinates;
+
+impl PixelKernel<i32> for Coordinates {
+    type Output<const N: usize>
+        = Simd<u32, N>
+    where
+        LaneCount<N>: SupportedLaneCount;
+
+    fn pixel<const N: usize>(&self, state: &SimdTrianglePixelState<i32, N>) -> Simd<u32, N>
+    where
+        LaneCount<N>: SupportedLaneCount,
+    {
+        state.pixel.x.cast::<u32>() << 8 | state.pixel.y.cast::<u32>()
+    }
+}
+
+/// Draws to a tile at an offset, with dimensions that aren't multiples of any lane count.
+fn draw(rasterize: impl FnOnce(Tile<'_>)) -> Vec<u32> {
+    let mut dst = vec![0; SIZE * SIZE];
+
+    rasterize(Tile {
+        targets: [RenderTarget::new(&mut dst)],
+        depth: None,
+        hiz: None,
+        fragments: None,
+        dst_width: SIZE,
+        position: Vec2 { x: 5, y: 7 },
+        dimensions: Vec2 { x: 77, y: 61 },
+    });
+
+    dst
+}
+
+#[test]
+fn levels_match() {
+    let expected = draw(|tile| {
+        SimdTriangleRasterizer::<i32, 8>::default()
+            .rasterize(tile, &TRIANGLE, |state| Coordinates.pixel(state))
+    });
+
+    assert!(expected.iter().filter(|c| **c != 0).count() > 1000);
+
+    for level in [
+        SimdLevel::Fallback,
+        SimdLevel::Sse2,
+        SimdLevel::Avx2,
+        SimdLevel::Avx512,
+        SimdLevel::Neon,
+    ] {
+        let rows = draw(|tile| {
+            DispatchTriangleRasterizer::<i32>::with_level(level).rasterize(
+                tile,
+                &TRIANGLE,
+                &Coordinates,
+            )
+        });
+        let blocks = draw(|tile| {
+            DispatchTriangleRasterizer::<i32, BlockLayout>::with_level(level).rasterize(
+                tile,
+                &TRIANGLE,
+                &Coordinates,
+            )
+        });
+
+        assert_eq!(rows, expected, "{level:?}");
+        assert_eq!(blocks, expected, "{level:?}");
+    }
+}
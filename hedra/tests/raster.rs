@@ -0,0 +1,412 @@
+#![feature(portable_simd)]
+
+//! Compares the output of the SIMD triangle rasterizer across settings that should draw the
+//! same pixels.
+
+use std::simd::{cmp::SimdPartialOrd, Mask, Select, Simd, SimdElement};
+
+use hedra::{
+    math::{Vec2, Vec3},
+    raster::{
+        simd::{SimdTrianglePixelState, SimdTriangleRasterizer},
+        visibility::Visibility,
+        BlendMode, ColorMask, Compare, Conservative, CullMode, FillMode, HiZ, Rasterizer,
+        RenderTarget, Tile,
+    },
+};
+
+const SIZE: usize = 32;
+
+fn tile<'a>(
+    dst: &'a mut [u32],
+    depth: Option<&'a mut [f32]>,
+    hiz: Option<&'a mut HiZ>,
+) -> Tile<'a> {
+    Tile {
+        targets: [RenderTarget::new(dst)],
+        depth,
+        hiz,
+        fragments: None,
+        dst_width: SIZE,
+        position: Vec2 { x: 0, y: 0 },
+        dimensions: Vec2 { x: SIZE, y: SIZE },
+    }
+}
+
+#[test]
+fn hiz_expanded_traversal() {
+    // depth only drops below the cleared depth past the bottom edge, in the row of pixels
+    // that expanded traversals cover
+    let list = [
+        Vec3 {
+            x: 4.0,
+            y: 4.0,
+            z: 0.89,
+        },
+        Vec3 {
+            x: 28.0,
+            y: 11.8,
+            z: 0.5,
+        },
+        Vec3 {
+            x: 4.0,
+            y: 11.8,
+            z: 0.5,
+        },
+    ];
+    let draw = |configure: fn(&mut SimdTriangleRasterizer<f32, 8>), hiz: bool| {
+        let mut dst = vec![0; SIZE * SIZE];
+        let mut depth = vec![0.5; SIZE * SIZE];
+        let mut buffer = HiZ::new(Vec2 { x: SIZE, y: SIZE }, Vec2 { x: 8, y: 1 }, 0.5);
+        let mut rasterizer = SimdTriangleRasterizer::<f32, 8>::default();
+
+        rasterizer.state.cull_mode = CullMode::None;
+        rasterizer.state.depth.compare = Compare::Less;
+        configure(&mut rasterizer);
+        rasterizer.rasterize(
+            tile(&mut dst, Some(&mut depth), hiz.then_some(&mut buffer)),
+            &list,
+            |_: &SimdTrianglePixelState<f32, 8>| Simd::splat(1),
+        );
+
+        dst
+    };
+
+    let modes: [fn(&mut SimdTriangleRasterizer<f32, 8>); 3] = [
+        |rasterizer| rasterizer.state.conservative = Conservative::Overestimate,
+        |rasterizer| rasterizer.state.antialias = true,
+        |rasterizer| rasterizer.state.fill_mode = FillMode::Wireframe,
+    ];
+
+    for configure in modes {
+        let expected = draw(configure, false);
+
+        assert!(expected.contains(&1));
+        assert_eq!(draw(configure, true), expected);
+    }
+}
+
+/// Triangle with an apex so sharp that the lines through its edges stay within half a pixel of
+/// the row it's in for over 10 pixels past it.
+const SLIVER: [Vec3<f32>; 3] = [
+    Vec3 {
+        x: 2.0,
+        y: 15.0,
+        z: 0.0,
+    },
+    Vec3 {
+        x: 20.0,
+        y: 16.0,
+        z: 0.0,
+    },
+    Vec3 {
+        x: 2.0,
+        y: 17.0,
+        z: 0.0,
+    },
+];
+
+/// Returns the rightmost column drawn to when drawing [SLIVER].
+fn sliver_extent(configure: fn(&mut SimdTriangleRasterizer<f32, 8>)) -> Option<usize> {
+    let mut dst = vec![0; SIZE * SIZE];
+    let mut rasterizer = SimdTriangleRasterizer::<f32, 8>::default();
+
+    rasterizer.state.cull_mode = CullMode::None;
+    configure(&mut rasterizer);
+    rasterizer.rasterize(
+        tile(&mut dst, None, None),
+        &SLIVER,
+        |state: &SimdTrianglePixelState<f32, 8>| {
+            state
+                .coverage
+                .simd_gt(Simd::splat(0.0))
+                .select(Simd::splat(1), Simd::splat(0))
+        },
+    );
+
+    dst.iter()
+        .enumerate()
+        .filter(|(_, c)| **c != 0)
+        .map(|(i, _)| i % SIZE)
+        .max()
+}
+
+#[test]
+fn antialiased_corners() {
+    assert_eq!(
+        sliver_extent(|rasterizer| rasterizer.state.antialias = true),
+        Some(20)
+    );
+}
+
+#[test]
+fn wireframe_corners() {
+    assert_eq!(
+        sliver_extent(|rasterizer| rasterizer.state.fill_mode = FillMode::Wireframe),
+        Some(20)
+    );
+    assert_eq!(
+        sliver_extent(|rasterizer| {
+            rasterizer.state.fill_mode = FillMode::Wireframe;
+            rasterizer.state.antialias = true;
+        }),
+        Some(20)
+    );
+}
+
+#[test]
+fn visibility_ignores_state() {
+    let draw = |configure: fn(&mut SimdTriangleRasterizer<f32, 8>), write_mask| {
+        let mut dst = vec![0; SIZE * SIZE];
+        let mut depth = vec![1.0; SIZE * SIZE];
+        let mut rasterizer = SimdTriangleRasterizer::<f32, 8>::default();
+
+        rasterizer.state.cull_mode = CullMode::None;
+        configure(&mut rasterizer);
+
+        let state = rasterizer.state;
+        let mut tile = tile(&mut dst, Some(&mut depth), None);
+
+        tile.targets[0].write_mask = write_mask;
+        rasterizer.rasterize_visibility(tile, &SLIVER, 3);
+
+        assert_eq!(rasterizer.state, state);
+
+        dst
+    };
+
+    let expected = draw(|_| {}, ColorMask::ALL);
+
+    assert!(expected.iter().any(|c| *c != 0));
+    assert_eq!(
+        draw(
+            |rasterizer| {
+                rasterizer.state.blend = BlendMode::Additive;
+                rasterizer.state.fill_mode = FillMode::Wireframe;
+                rasterizer.state.antialias = true;
+            },
+            ColorMask::NONE
+        ),
+        expected
+    );
+}
+
+#[test]
+fn instance_argument() {
+    let mut dst = vec![0; SIZE * SIZE];
+    let mut rasterizer = SimdTriangleRasterizer::<f32, 8>::default();
+    let instance = |state: &SimdTrianglePixelState<f32, 8>| Simd::splat(state.instance as u32 + 1);
+
+    rasterizer.state.cull_mode = CullMode::None;
+    rasterizer.rasterize_instance(tile(&mut dst, None, None), &SLIVER, 5, instance);
+
+    assert!(dst.contains(&6));
+
+    rasterizer.rasterize(tile(&mut dst, None, None), &SLIVER, instance);
+
+    assert!(dst.iter().all(|c| *c != 6));
+
+    dst.fill(Visibility::NONE);
+    rasterizer.rasterize_visibility(tile(&mut dst, None, None), &SLIVER, 7);
+
+    let visibilities: Vec<_> = dst.into_iter().filter_map(Visibility::unpack).collect();
+
+    assert!(!visibilities.is_empty());
+    assert!(visibilities.iter().all(|v| v.instance == 7));
+}
+
+#[test]
+fn hiz_clear_depth() {
+    let quad = |z| {
+        [(0.0, 0.0), (SIZE as f32, 0.0), (0.0, SIZE as f32)]
+            .into_iter()
+            .chain([
+                (SIZE as f32, 0.0),
+                (SIZE as f32, SIZE as f32),
+                (0.0, SIZE as f32),
+            ])
+            .map(|(x, y)| Vec3 { x, y, z })
+            .collect::<Vec<_>>()
+    };
+    let draw = |hiz: bool| {
+        let mut dst = vec![0; SIZE * SIZE];
+        let mut depth = vec![1.0; SIZE * SIZE];
+        let mut buffer = HiZ::new(Vec2 { x: SIZE, y: SIZE }, Vec2 { x: 8, y: 1 }, 1.0);
+        let mut rasterizer = SimdTriangleRasterizer::<f32, 8>::default();
+
+        rasterizer.state.cull_mode = CullMode::None;
+        rasterizer.state.depth.compare = Compare::Less;
+        rasterizer.state.depth.write = true;
+
+        for (z, color) in [(0.3, 1), (0.5, 2)] {
+            let mut tile = tile(&mut dst, Some(&mut depth), hiz.then_some(&mut buffer));
+
+            // clears the left half of the target between the draws, straddling blocks
+            tile.dimensions.x = SIZE / 2 + 4;
+
+            if color == 2 {
+                tile.clear_depth(0.9);
+            }
+
+            rasterizer.rasterize(tile, &quad(z), |_: &SimdTrianglePixelState<f32, 8>| {
+                Simd::splat(color)
+            });
+        }
+
+        dst
+    };
+
+    let expected = draw(false);
+
+    assert!(expected.contains(&2));
+    assert_eq!(draw(true), expected);
+}
+
+/// Returns whether the pixel at `p` is covered by `triangle`, evaluating its edges exactly and
+/// including pixels on its top and left edges.
+fn covers([v1, v2, v3]: [Vec2<i128>; 3], p: Vec2<i128>) -> bool {
+    let area = (v3.x - v2.x) * (v1.y - v2.y) - (v3.y - v2.y) * (v1.x - v2.x);
+    let (v2, v3) = if area > 0 { (v2, v3) } else { (v3, v2) };
+
+    area != 0
+        && [(v2, v3), (v3, v1), (v1, v2)].into_iter().all(|(a, b)| {
+            let edge = (a.y - b.y) * (p.x - a.x) + (b.x - a.x) * (p.y - a.y);
+            let top_left = b.y < a.y || (b.y == a.y && b.x > a.x);
+
+            edge > 0 || (edge == 0 && top_left)
+        })
+}
+
+/// Returns the pixels of the target covered by `triangles` according to [covers].
+fn reference(triangles: &[[Vec2<i128>; 3]]) -> Vec<u32> {
+    (0..SIZE * SIZE)
+        .map(|i| {
+            let p = Vec2 {
+                x: (i % SIZE) as i128,
+                y: (i / SIZE) as i128,
+            };
+
+            triangles.iter().filter(|t| covers(**t, p)).count() as u32
+        })
+        .collect()
+}
+
+/// Draws `triangles` with additive blending, so that pixels count the triangles covering them.
+fn count<T: SimdElement>(triangles: &[[Vec2<T>; 3]]) -> Vec<u32>
+where
+    SimdTriangleRasterizer<T, 8>: Default
+        + for<'a> Rasterizer<
+            'a,
+            T,
+            State = SimdTrianglePixelState<T, 8>,
+            Color = Simd<u32, 8>,
+            Mask = Mask<i32, 8>,
+        >,
+{
+    let mut dst = vec![0; SIZE * SIZE];
+    let mut rasterizer = SimdTriangleRasterizer::<T, 8>::default();
+
+    rasterizer.state.cull_mode = CullMode::None;
+    rasterizer.state.blend = BlendMode::Additive;
+
+    for triangle in triangles {
+        rasterizer.rasterize(tile(&mut dst, None, None), triangle, |_| Simd::splat(1));
+    }
+
+    dst
+}
+
+fn cast<T, U>(triangles: &[[Vec2<T>; 3]], f: impl Fn(T) -> U) -> Vec<[Vec2<U>; 3]>
+where
+    T: Copy,
+{
+    triangles
+        .iter()
+        .map(|t| {
+            t.map(|v| Vec2 {
+                x: f(v.x),
+                y: f(v.y),
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn i16_fallback() {
+    // edges whose coefficients overflow i16, one of them crossing the tile along y = x + 10
+    // through the centers of pixels
+    let triangles: [[Vec2<i16>; 3]; 2] = [
+        [
+            Vec2 {
+                x: -20000,
+                y: -19990,
+            },
+            Vec2 { x: 20000, y: 20010 },
+            Vec2 {
+                x: 20000,
+                y: -20000,
+            },
+        ],
+        [
+            Vec2 { x: -3, y: 2 },
+            Vec2 { x: 30, y: -1 },
+            Vec2 { x: 12, y: 28 },
+        ],
+    ];
+    let expected = reference(&cast(&triangles, i128::from));
+
+    assert!(expected.contains(&1));
+    assert_eq!(count(&triangles), expected);
+}
+
+#[test]
+fn far_vertices() {
+    // beyond the guard band of the setup in i64, one of the triangles crossing the tile along
+    // y = x + 10 and the other covering all of it
+    let far = 2_000_000_000;
+    let triangles = [
+        [
+            Vec2 {
+                x: -far,
+                y: -far + 10,
+            },
+            Vec2 {
+                x: far,
+                y: far + 10,
+            },
+            Vec2 { x: far, y: -far },
+        ],
+        [
+            Vec2 { x: -far, y: -far },
+            Vec2 { x: far, y: -far },
+            Vec2 { x: 0, y: far },
+        ],
+    ];
+    let expected = reference(&cast(&triangles, i128::from));
+
+    assert!(expected.contains(&1) && expected.contains(&2));
+    assert_eq!(count(&triangles), expected);
+}
+
+#[test]
+fn top_left_shared_edges() {
+    // square split along both diagonals, whose shared edges go through the centers of pixels
+    let [a, b, c, d, center] =
+        [(2, 2), (30, 2), (30, 30), (2, 30), (16, 16)].map(|(x, y)| Vec2 { x, y });
+    let triangles = [
+        [center, a, b],
+        [center, b, c],
+        [center, c, d],
+        [center, d, a],
+    ];
+    let expected = reference(&cast(&triangles, i128::from));
+
+    // the square's top and left edges are covered, its bottom and right ones aren't
+    assert!(expected.iter().enumerate().all(|(i, c)| {
+        let (x, y) = (i % SIZE, i / SIZE);
+
+        *c == ((2..30).contains(&x) && (2..30).contains(&y)) as u32
+    }));
+    assert_eq!(count(&triangles), expected);
+    assert_eq!(count(&cast(&triangles, |v| v as f32)), expected);
+}
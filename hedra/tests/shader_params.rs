@@ -0,0 +1,229 @@
+#![feature(portable_simd)]
+
+//! Covers every arm of [shader_params!], building parameters from vertex states fetched from
+//! buffers and from the pixel states of the SIMD rasterizer.
+
+use std::simd::{num::SimdFloat, Simd};
+
+use hedra::{
+    math::{Vec2, Vec3},
+    pipeline::vertex::{Attributes, FetchVertex, FetchedVertex, VertexFetch, VertexLayout},
+    raster::{
+        simd::{SimdTrianglePixelState, SimdTriangleRasterizer},
+        FillMode, Rasterizer, RenderTarget, Tile,
+    },
+    shader_params,
+};
+
+type State = SimdTrianglePixelState<f32, 8>;
+
+struct Vertex;
+
+impl FetchVertex for Vertex {
+    fn fetch(_: &mut Attributes<'_>) -> Self {
+        Self
+    }
+}
+
+shader_params!(Empty<FetchedVertex<'_, Vertex>> {});
+
+shader_params!(VertexParams<FetchedVertex<'_, Vertex>> {
+    @vertex_index vertex: u32,
+    @instance_index instance: usize,
+    uniform: f32
+});
+
+shader_params!(pub PixelParams<&State> {
+    @pixel pixel: Vec2<Simd<f32, 8>>,
+    @frag_coord frag_coord: Vec3<Simd<f32, 8>>,
+    @barycentric barycentric: Vec3<Simd<f32, 8>>,
+    @primitive_index primitive: u32,
+    @instance_index instance: usize,
+    @front_facing front_facing: bool,
+    @sample_index sample: u8,
+});
+
+shader_params!(pub(crate) Facing<&State> { @front_facing front_facing: u32 });
+
+mod nested {
+    use super::State;
+
+    hedra::shader_params!(pub(super) Primitive<&State> { uniform: u32, @primitive_index primitive: usize });
+}
+
+fn state() -> State {
+    State {
+        pixel: Vec2 {
+            x: Simd::splat(3.0),
+            y: Simd::splat(4.0),
+        },
+        position: Vec2 {
+            x: Simd::splat(3.0),
+            y: Simd::splat(4.0),
+        },
+        depth: Simd::splat(0.5),
+        coverage: Simd::splat(1.0),
+        barycentric: Vec3 {
+            x: Simd::splat(0.25),
+            y: Simd::splat(0.5),
+            z: Simd::splat(0.25),
+        },
+        primitive: 7,
+        instance: 2,
+        front_facing: true,
+    }
+}
+
+fn draw(fill_mode: FillMode, pixel: impl Fn(&State) -> Simd<u32, 8>) -> Vec<u32> {
+    let mut dst = vec![0; 32 * 32];
+    let mut rasterizer = SimdTriangleRasterizer::<f32, 8>::default();
+
+    rasterizer.state.fill_mode = fill_mode;
+    rasterizer.rasterize(
+        Tile {
+            targets: [RenderTarget::new(&mut dst)],
+            depth: None,
+            hiz: None,
+            fragments: None,
+            dst_width: 32,
+            position: Vec2 { x: 0, y: 0 },
+            dimensions: Vec2 { x: 32, y: 32 },
+        },
+        &TRIANGLE,
+        pixel,
+    );
+
+    dst
+}
+
+const TRIANGLE: [Vec3<f32>; 3] = [
+    Vec3 {
+        x: 2.0,
+        y: 1.0,
+        z: 0.25,
+    },
+    Vec3 {
+        x: 29.5,
+        y: 6.0,
+        z: 0.5,
+    },
+    Vec3 {
+        x: 9.0,
+        y: 30.0,
+        z: 0.75,
+    },
+];
+
+fn assert_close(a: Simd<f32, 8>, b: Simd<f32, 8>) {
+    assert!(
+        (a - b).abs().reduce_max() < 1e-3,
+        "{a:?} isn't close to {b:?}"
+    );
+}
+
+#[test]
+fn no_fields() {
+    let layout = VertexLayout::default();
+    let Empty {} = VertexFetch::new(&layout, &[]).vertex::<Vertex>(0, 0).into();
+}
+
+#[test]
+fn vertex_builtins() {
+    let layout = VertexLayout::default();
+    let params = VertexParams::from(VertexFetch::new(&layout, &[]).vertex::<Vertex>(5, 2));
+
+    assert_eq!(params.vertex, 5);
+    assert_eq!(params.instance, 2);
+    assert_eq!(params.uniform, 0.0);
+}
+
+#[test]
+fn pixel_builtins() {
+    let state = state();
+    let params = PixelParams::from(&state);
+
+    assert_eq!(params.pixel, state.pixel);
+    assert_eq!(
+        params.frag_coord,
+        Vec3 {
+            x: Simd::splat(3.0),
+            y: Simd::splat(4.0),
+            z: Simd::splat(0.5),
+        }
+    );
+    assert_eq!(params.barycentric, state.barycentric);
+    assert_eq!(params.primitive, 7);
+    assert_eq!(params.instance, 2);
+    assert!(params.front_facing);
+    assert_eq!(params.sample, 0);
+}
+
+#[test]
+fn restricted_visibility() {
+    let mut state = state();
+
+    assert_eq!(Facing::from(&state).front_facing, 1);
+
+    state.front_facing = false;
+
+    let params = nested::Primitive::from(&state);
+
+    assert_eq!(Facing::from(&state).front_facing, 0);
+    assert_eq!(params.primitive, 7);
+    assert_eq!(params.uniform, 0);
+}
+
+#[test]
+fn rasterized_barycentric() {
+    let dst = draw(FillMode::Solid, |state| {
+        let PixelParams {
+            frag_coord,
+            barycentric: weight,
+            ..
+        } = state.into();
+        let [v1, v2, v3] = TRIANGLE.map(|v| Vec3 {
+            x: Simd::splat(v.x),
+            y: Simd::splat(v.y),
+            z: Simd::splat(v.z),
+        });
+
+        assert_close(weight.x + weight.y + weight.z, Simd::splat(1.0));
+        assert_close(
+            weight.x * v1.x + weight.y * v2.x + weight.z * v3.x,
+            frag_coord.x,
+        );
+        assert_close(
+            weight.x * v1.y + weight.y * v2.y + weight.z * v3.y,
+            frag_coord.y,
+        );
+        assert_close(
+            weight.x * v1.z + weight.y * v2.z + weight.z * v3.z,
+            frag_coord.z,
+        );
+
+        Simd::splat(1)
+    });
+
+    assert!(dst.contains(&1));
+}
+
+#[test]
+fn point_barycentric() {
+    let dst = draw(FillMode::Point, |state| {
+        let PixelParams { barycentric, .. } = state.into();
+        let weights = [barycentric.x, barycentric.y, barycentric.z];
+
+        assert_eq!(
+            weights.iter().filter(|w| **w == Simd::splat(1.0)).count(),
+            1
+        );
+        assert_eq!(
+            weights.iter().filter(|w| **w == Simd::splat(0.0)).count(),
+            2
+        );
+
+        Simd::splat(1)
+    });
+
+    assert_eq!(dst.iter().filter(|c| **c == 1).count(), 3);
+}
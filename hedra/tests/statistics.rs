@@ -0,0 +1,40 @@
+use hedra::raster::{Query, Statistics};
+
+#[test]
+fn empty_ratios() {
+    let statistics = Statistics::default();
+
+    assert_eq!(statistics.lane_efficiency(), 0.0);
+    assert_eq!(statistics.vertex_cache_hit_rate(), 0.0);
+}
+
+#[test]
+fn ratios() {
+    let statistics = Statistics {
+        lanes_shaded: 3,
+        lanes_masked: 1,
+        vertex_cache_hits: 1,
+        vertex_cache_misses: 3,
+        ..Default::default()
+    };
+
+    assert_eq!(statistics.lane_efficiency(), 0.75);
+    assert_eq!(statistics.vertex_cache_hit_rate(), 0.25);
+}
+
+#[test]
+fn query_reset() {
+    let mut statistics = Statistics {
+        samples_passed: 10,
+        triangles_rasterized: 2,
+        ..Default::default()
+    };
+    let query = Query::begin(&statistics);
+
+    statistics = Statistics {
+        samples_passed: 4,
+        ..Default::default()
+    };
+
+    assert_eq!(query.end(&statistics), Statistics::default());
+}
@@ -0,0 +1,78 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use hedra::{
+    buffer::BufferGuard,
+    math::Vec2,
+    pipeline::vertex::{
+        Attributes, FetchVertex, Format, StepMode, VertexAttribute, VertexBuffer,
+        VertexBufferLayout, VertexFetch, VertexLayout,
+    },
+};
+
+#[derive(Debug, PartialEq)]
+struct Vertex {
+    position: Vec2<f32>,
+    offset: u16,
+}
+
+impl FetchVertex for Vertex {
+    fn fetch(attributes: &mut Attributes<'_>) -> Self {
+        Self {
+            position: attributes.read(),
+            offset: attributes.read(),
+        }
+    }
+}
+
+#[test]
+fn locked_buffer() {
+    let layout = VertexLayout {
+        buffers: vec![
+            VertexBufferLayout {
+                stride: 8,
+                step: StepMode::Vertex,
+            },
+            VertexBufferLayout {
+                stride: 2,
+                step: StepMode::Instance(1),
+            },
+        ],
+        attributes: vec![
+            VertexAttribute {
+                buffer: 0,
+                offset: 0,
+                format: Format::F32,
+                components: 2,
+            },
+            VertexAttribute {
+                buffer: 1,
+                offset: 0,
+                format: Format::U16,
+                components: 1,
+            },
+        ],
+    };
+    let mut positions: Vec<u8> = [1.0f32, 2.0, 3.0, 4.0]
+        .into_iter()
+        .flat_map(f32::to_le_bytes)
+        .collect();
+    let offsets: Vec<u8> = [5u16, 6].into_iter().flat_map(u16::to_le_bytes).collect();
+    let unlocked = AtomicBool::new(false);
+
+    {
+        let guard = BufferGuard::new(&mut positions, || unlocked.store(true, Ordering::Relaxed));
+        let buffers: [&dyn VertexBuffer; 2] = [&guard, &offsets];
+        let fetch = VertexFetch::new(&layout, &buffers);
+
+        assert_eq!(
+            fetch.fetch::<Vertex>(1, 1),
+            Vertex {
+                position: Vec2 { x: 3.0, y: 4.0 },
+                offset: 6,
+            }
+        );
+        assert!(!unlocked.load(Ordering::Relaxed));
+    }
+
+    assert!(unlocked.load(Ordering::Relaxed));
+}